@@ -0,0 +1,252 @@
+//! Benchmarks of parsing, responding, and processing whole connections, to spot regressions.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::borrow::Cow;
+use std::hint::black_box;
+use std::io::{IoSlice, Read as _, Write as _};
+use std::os::unix::net::UnixStream;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+
+use async_std::io::{self, Cursor, Read, Write};
+use async_std::task;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion, Throughput};
+
+use http_404::{process, Config, RequestMessage, ResponseMessage};
+
+/// Represents the system allocator, counting allocations on the way.
+///
+/// It is a relaxed increment per allocation, cheap enough not to skew the timings.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Represents an in-memory stream, reading from a given input and discarding the output.
+struct Duplex {
+    input: Cursor<Vec<u8>>,
+    written: usize,
+}
+
+impl Read for Duplex {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.input).poll_read(cx, buf)
+    }
+}
+
+impl Write for Duplex {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        self.written += buf.len();
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Returns a GET request head with a path of a given length.
+fn request(length: usize) -> Vec<u8> {
+    let path = "a".repeat(length.saturating_sub(1));
+
+    format!("GET /{path} HTTP/1.1\r\nHost: example.com\r\nUser-Agent: bench\r\n\r\n").into_bytes()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let mut group = c.benchmark_group("RequestMessage::from");
+
+    for (name, length) in [
+        ("short", 8),
+        ("long", 4096),
+        ("max", RequestMessage::PATH_LIMIT),
+    ] {
+        let bytes = request(length);
+
+        group.throughput(Throughput::Bytes(bytes.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &bytes, |b, bytes| {
+            b.iter(|| {
+                RequestMessage::from(black_box(bytes.as_slice()))
+                    .headers
+                    .len()
+            })
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_response(c: &mut Criterion) {
+    let mut group = c.benchmark_group("RequestMessage::response");
+    let config = Config {
+        root: Some(ResponseMessage {
+            body: Cow::Borrowed(b"<h1>Hello</h1>"),
+            ..ResponseMessage::from_code(200)
+        }),
+        max_uri_bytes: 16,
+        ..Config::default()
+    };
+
+    for (name, bytes) in [
+        ("200", b"GET / HTTP/1.1\r\n\r\n".as_slice()),
+        ("204", b"GET /healthz HTTP/1.1\r\n\r\n"),
+        ("400", b"GET /%zz HTTP/1.1\r\n\r\n"),
+        ("404", b"GET /foo HTTP/1.1\r\n\r\n"),
+        ("405", b"CONNECT example.com:443 HTTP/1.1\r\n\r\n"),
+        ("414", b"GET /abcdefghijklmnop HTTP/1.1\r\n\r\n"),
+        ("501", b"PROPFIND / HTTP/1.1\r\n\r\n"),
+        ("505", b"GET / HTTP/1.2\r\n\r\n"),
+    ] {
+        let request = RequestMessage::from(bytes);
+
+        assert!(request.response(&config).code.to_string() == name);
+
+        group.bench_function(name, |b| {
+            b.iter(|| black_box(&request).response(&config).code)
+        });
+    }
+
+    group.finish();
+}
+
+/// Processes a connection of given pipelined requests, and returns how many allocations it took.
+fn allocations(bytes: &[u8], config: &Arc<Config>) -> usize {
+    let mut stream = Duplex {
+        input: Cursor::new(bytes.to_vec()),
+        written: 0,
+    };
+
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    task::block_on(process(&mut stream, None, config.clone())).unwrap();
+
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench_process(c: &mut Criterion) {
+    let mut group = c.benchmark_group("process");
+    let config = Arc::new(Config {
+        access_log: false,
+        ..Config::default()
+    });
+    let request = b"GET /foo HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+    // NOTE: every connection has a fixed cost, which the difference between two cancels out
+    let (ten, hundred) = (
+        allocations(&request.repeat(10), &config),
+        allocations(&request.repeat(100), &config),
+    );
+    println!(
+        "process: {:.2} allocations per keep-alive request",
+        (hundred - ten) as f64 / 90.0
+    );
+
+    // NOTE: pipelined on a single connection, so the per-request cost is what adds up
+    for count in [1, 100] {
+        let bytes = request.repeat(count);
+
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(count), &bytes, |b, bytes| {
+            b.iter_batched(
+                || Duplex {
+                    input: Cursor::new(bytes.clone()),
+                    written: 0,
+                },
+                |mut stream| {
+                    task::block_on(process(&mut stream, None, config.clone())).unwrap();
+                    stream.written
+                },
+                BatchSize::SmallInput,
+            )
+        });
+    }
+
+    group.finish();
+}
+
+/// Returns a socket whose peer end is drained (by a thread of its own) until it is closed.
+fn sink() -> UnixStream {
+    let (writer, mut reader) = UnixStream::pair().unwrap();
+
+    thread::spawn(move || {
+        let mut buffer = [0; 65536];
+        while reader.read(&mut buffer).is_ok_and(|read| read > 0) {}
+    });
+
+    writer
+}
+
+fn bench_write(c: &mut Criterion) {
+    let mut group = c.benchmark_group("write");
+    let head = ResponseMessage::from_code(200).to_bytes(&[
+        b"Connection: keep-alive",
+        b"Date: Sun, 06 Nov 1994 08:49:37 GMT",
+        b"X-Request-Id: 0000000000000000000000000",
+        b"Server: 404-container",
+    ]);
+
+    // NOTE: what process() picks between, i.e. a copy of the body, or a slice of its own
+    for length in [256, 512, 1024, 2048, 4096, 8192, 16384] {
+        let body = vec![b'a'; length];
+        let mut writer = sink();
+        let mut buffer = Vec::with_capacity(head.len() + length);
+
+        group.throughput(Throughput::Bytes((head.len() + length) as u64));
+        group.bench_with_input(BenchmarkId::new("concat", length), &body, |b, body| {
+            b.iter(|| {
+                buffer.clear();
+                buffer.extend_from_slice(&head);
+                buffer.extend_from_slice(body);
+                writer.write_all(&buffer).unwrap();
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("vectored", length), &body, |b, body| {
+            b.iter(|| {
+                let mut slices = [IoSlice::new(&head), IoSlice::new(body)];
+                let mut slices = slices.as_mut_slice();
+
+                while !slices.is_empty() {
+                    let written = writer.write_vectored(slices).unwrap();
+                    IoSlice::advance_slices(&mut slices, written);
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_response,
+    bench_process,
+    bench_write
+);
+criterion_main!(benches);
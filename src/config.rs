@@ -0,0 +1,1579 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use async_tls::TlsAcceptor;
+use toml::{Table, Value};
+
+use crate::http::{is_host, is_tchar, RequestMessage, ResponseMessage, RESP_404};
+use crate::limit::Rate;
+use crate::log::{Format, Level};
+use crate::tls;
+use crate::utils;
+
+const TYPE_HTML: &[u8] = b"Content-Type: text/html; charset=utf-8";
+
+/// The largest buffer to read a connection into, i.e. a mebibyte.
+const READ_BUFFER_MAX: usize = 1024 * 1024;
+
+/// Path of an optional file with environment variables, relative to the working directory.
+const ENV_FILE: &str = ".env";
+
+/// Names of all settings, as environment variables, and as lowercase configuration file keys.
+const NAMES: [&str; 63] = [
+    "PORT",
+    "BIND",
+    "UNIX_SOCKET",
+    "REUSE_ADDR",
+    "REUSE_PORT",
+    "WORKERS",
+    "LISTEN_BACKLOG",
+    "BIND_RETRIES",
+    "HEALTH_PATH",
+    "HEALTH_JSON",
+    "LIVENESS_PATH",
+    "READINESS_PATH",
+    "SERVER_HEADER",
+    "EXTRA_HEADERS",
+    "CORS_ORIGIN",
+    "READ_TIMEOUT",
+    "KEEPALIVE_TIMEOUT",
+    "KEEPALIVE_MAX",
+    "SHUTDOWN_TIMEOUT",
+    "WATCHDOG_SECS",
+    "WATCHDOG_EXIT",
+    "MAX_URI_BYTES",
+    "MAX_HEADER_BYTES",
+    "STRICT_LINE_ENDINGS",
+    "MAX_BODY_BYTES",
+    "READ_BUFFER_BYTES",
+    "MAX_CONNECTIONS",
+    "RATE_LIMIT",
+    "RATE_BURST",
+    "PER_IP_RATE",
+    "PER_IP_BURST",
+    "TCP_NODELAY",
+    "TCP_KEEPALIVE_SECS",
+    "DEFAULT_STATUS",
+    "BODY_404",
+    "BODY_404_TYPE",
+    "ROOT_STATUS",
+    "ROOT_BODY",
+    "ROUTES",
+    "VHOSTS",
+    "STRICT_HOST",
+    "NORMALIZE_PATH",
+    "FORCE_HTTPS",
+    "RETRY_AFTER",
+    "RESPONSE_DELAY_MS",
+    "FAULT_RATE",
+    "FAULT_INCLUDE_HEALTH",
+    "COMPRESS",
+    "ACCESS_LOG",
+    "LOG_FORMAT",
+    "TRUST_PROXY",
+    "PROXY_PROTOCOL",
+    "LOG_LEVEL",
+    "LOG_ERRORS",
+    "METRICS_PATH",
+    "STATS",
+    "STATS_PATH",
+    "ECHO",
+    "ALLOW_TRACE",
+    "ADMIN_PATH",
+    "ADMIN_TOKEN",
+    "TLS_CERT",
+    "TLS_KEY",
+];
+
+/// Parses a `.env` file, i.e. `NAME=value` lines, with values optionally quoted.
+///
+/// Blank lines and `#` comments are skipped, and so is an `export` before a name. Double quotes
+/// allow `\n`, `\"`, and `\\` escapes, single ones take everything as is, up to the next quote.
+fn parse_env_file(text: &str) -> Result<HashMap<String, String>, String> {
+    let mut vars = HashMap::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let invalid = || format!("Invalid {ENV_FILE} line {}", index + 1);
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (name, value) = line.split_once('=').ok_or_else(invalid)?;
+        let name = name.trim();
+        let value = value.trim();
+
+        if name.is_empty()
+            || !name
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+        {
+            return Err(invalid());
+        }
+
+        let value = if let Some(rest) = value.strip_prefix('"') {
+            let mut unquoted = String::new();
+            let mut chars = rest.chars();
+
+            loop {
+                match chars.next().ok_or_else(invalid)? {
+                    '"' => break,
+                    '\\' => match chars.next().ok_or_else(invalid)? {
+                        'n' => unquoted.push('\n'),
+                        char @ ('"' | '\\') => unquoted.push(char),
+                        _ => return Err(invalid()),
+                    },
+                    char => unquoted.push(char),
+                }
+            }
+
+            // NOTE: anything but a comment after the closing quote is most likely a mistake
+            match chars.as_str().trim_start() {
+                rest if rest.is_empty() || rest.starts_with('#') => unquoted,
+                _ => return Err(invalid()),
+            }
+        } else if let Some(rest) = value.strip_prefix('\'') {
+            let (unquoted, rest) = rest.split_once('\'').ok_or_else(invalid)?;
+
+            match rest.trim_start() {
+                rest if rest.is_empty() || rest.starts_with('#') => unquoted.to_string(),
+                _ => return Err(invalid()),
+            }
+        } else {
+            // NOTE: a `#` only starts a comment after a space, so `a#b` stays a value
+            let end = value.find(" #").unwrap_or(value.len());
+            value[..end].trim_end().to_string()
+        };
+
+        vars.insert(name.to_string(), value);
+    }
+
+    Ok(vars)
+}
+
+/// Represents where settings come from, i.e. the environment, then a `.env` file, and then a
+/// configuration file.
+#[derive(Default)]
+struct Source {
+    env_file: HashMap<String, String>,
+    file: HashMap<String, String>,
+}
+
+impl Source {
+    /// Creates a new Source, with a `.env` file if there is one in the working directory, and a
+    /// configuration file if the `CONFIG` variable points at one.
+    fn new() -> Result<Source, String> {
+        // NOTE: the file is for local development, so it is fine for it to be absent
+        let env_file = match fs::read_to_string(ENV_FILE) {
+            Ok(text) => parse_env_file(&text)?,
+            Err(err) if err.kind() == ErrorKind::NotFound => HashMap::new(),
+            Err(err) => return Err(format!("Cannot read {ENV_FILE}: {err}")),
+        };
+        let source = Source {
+            env_file,
+            ..Source::default()
+        };
+
+        match source.var("CONFIG") {
+            Some(path) => {
+                let text = fs::read_to_string(&path)
+                    .map_err(|err| format!("Cannot read configuration file {path}: {err}"))?;
+
+                Ok(Source {
+                    file: Source::parse(&text)?.file,
+                    ..source
+                })
+            }
+            None => Ok(source),
+        }
+    }
+
+    /// Parses a TOML configuration file, with settings as top-level keys of scalar values.
+    fn parse(text: &str) -> Result<Source, String> {
+        let table: Table = text
+            .parse()
+            .map_err(|err| format!("Cannot parse configuration file: {err}"))?;
+
+        let file = table
+            .into_iter()
+            .map(|(key, value)| {
+                let name = key.to_ascii_uppercase();
+
+                // NOTE: a typo would otherwise go unnoticed, and the setting unapplied
+                if key != key.to_ascii_lowercase() || !NAMES.contains(&name.as_str()) {
+                    return Err(format!("Unknown configuration key: {key}"));
+                }
+
+                let value = match value {
+                    Value::String(value) => value,
+                    Value::Integer(value) => value.to_string(),
+                    Value::Float(value) => value.to_string(),
+                    Value::Boolean(value) => value.to_string(),
+                    _ => return Err(format!("Invalid configuration value: {key}")),
+                };
+
+                Ok((name, value))
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Source {
+            file,
+            ..Source::default()
+        })
+    }
+
+    /// Returns a setting value, taken from the environment first, then from the `.env` file, and
+    /// then from the configuration file.
+    fn var(&self, name: &str) -> Option<String> {
+        env::var(name)
+            .ok()
+            .or_else(|| self.env_file.get(name).cloned())
+            .or_else(|| self.file.get(name).cloned())
+    }
+}
+
+/// Parses a boolean flag, e.g. `1`, `true`, `on`, or `0`, `false`, `off`.
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" | "on" | "yes" => Some(true),
+        "" | "0" | "false" | "off" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+/// Parses a path to answer on, which must not be empty and must start with a slash.
+fn parse_path(value: String, name: &str) -> Result<Vec<u8>, String> {
+    if value.is_empty() {
+        Err(format!("Empty {name} path"))
+    } else if !value.starts_with('/') {
+        Err(format!("Invalid {name} path"))
+    } else {
+        Ok(value.into_bytes())
+    }
+}
+
+/// Parses a status code, which must be within the 100 to 599 range.
+fn parse_status(value: &str, name: &str) -> Result<u16, String> {
+    match value.parse() {
+        Ok(code @ 100..=599) => Ok(code),
+        _ => Err(format!("Invalid {name} status")),
+    }
+}
+
+/// Parses a rate of requests per second, zero for none, with an optional burst, which must not
+/// be zero, and defaults to the rate itself, i.e. a second worth of requests.
+///
+/// A burst without a rate is refused, and so is a rate over a billion, as the bucket interval
+/// would then round down to no time at all, i.e. no limit.
+fn parse_rate(
+    value: Option<String>,
+    burst: Option<String>,
+    name: &str,
+) -> Result<Option<Rate>, String> {
+    let per_second = match value.as_deref().map(str::parse) {
+        None if burst.is_some() => return Err(format!("Missing {name} limit")),
+        None => return Ok(None),
+        Some(Ok(per_second @ 0..=1_000_000_000)) => per_second,
+        Some(_) => return Err(format!("Invalid {name} limit")),
+    };
+    let burst = match burst.as_deref().map(str::parse) {
+        Some(_) if per_second == 0 => return Err(format!("Missing {name} limit")),
+        Some(Ok(0) | Err(_)) => return Err(format!("Invalid {name} burst")),
+        Some(Ok(burst)) => burst,
+        None => per_second,
+    };
+
+    Ok((per_second > 0).then_some(Rate { per_second, burst }))
+}
+
+/// Reads a body, which is either a path to an existing file, or the body itself.
+fn read_body(value: String) -> Result<Vec<u8>, String> {
+    if Path::new(&value).is_file() {
+        fs::read(&value).map_err(|err| format!("Cannot read body: {err}"))
+    } else {
+        Ok(value.into_bytes())
+    }
+}
+
+/// Sets a given body on a given response, along with its Content-Type header and entity tag,
+/// unless the body is empty, which fails for a bodiless response.
+///
+/// The response owns all of them, so they are dropped along with a Config replaced on reload.
+fn set_body(
+    response: &mut ResponseMessage<'static>,
+    body: Vec<u8>,
+    content_type: Cow<'static, [u8]>,
+) -> Result<(), String> {
+    if body.is_empty() {
+        return Ok(());
+    }
+
+    // NOTE: such a body would never be sent, so dropping it quietly would hide a mistake
+    if response.is_bodiless() {
+        return Err(format!("Body not allowed with status {}", response.code));
+    }
+
+    response.content_type = Some(content_type);
+    response.etag = Some(Cow::Owned(utils::etag(&body).into_bytes()));
+    response.body = Cow::Owned(body);
+
+    Ok(())
+}
+
+/// Parses a media type into a Content-Type header, which must not be empty or have any control
+/// characters, as it is written out as is.
+fn parse_type(value: &str, name: &str) -> Result<Vec<u8>, String> {
+    if value.trim().is_empty() || value.contains(|char: char| char.is_ascii_control()) {
+        return Err(format!("Invalid {name} body type"));
+    }
+
+    Ok(format!("Content-Type: {}", value.trim()).into_bytes())
+}
+
+/// Parses a `;`-separated list of `Name: Value` headers, with names being tokens.
+fn parse_headers(value: &str) -> Result<Vec<Vec<u8>>, String> {
+    value
+        .split(';')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (name, value) = entry
+                .split_once(':')
+                .ok_or_else(|| format!("Invalid extra header: {}", entry.trim()))?;
+            let (name, value) = (name.trim(), value.trim());
+
+            // NOTE: only tokens and no line breaks, so nothing can split a response
+            if name.is_empty() || !name.bytes().all(|byte| is_tchar(&byte)) {
+                Err(format!("Invalid extra header name: {name}"))
+            } else if value.contains(['\r', '\n']) {
+                Err(format!("Invalid extra header value: {name}"))
+            } else {
+                Ok(format!("{name}: {value}").into_bytes())
+            }
+        })
+        .collect()
+}
+
+/// Parses a `;`-separated list of `/prefix=status` routes, sorted from the longest prefix.
+fn parse_routes(value: &str) -> Result<Vec<(Vec<u8>, ResponseMessage<'static>)>, String> {
+    let mut routes = value
+        .split(';')
+        .filter(|entry| !entry.trim().is_empty())
+        .map(|entry| {
+            let (prefix, code) = entry
+                .split_once('=')
+                .ok_or_else(|| format!("Invalid route: {}", entry.trim()))?;
+            let prefix = parse_path(prefix.trim().to_string(), "route")?;
+            let code = parse_status(code.trim(), "route")?;
+
+            Ok((prefix, ResponseMessage::from_code(code)))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+
+    // NOTE: the same prefixes end up next to each other, so duplicates are easy to tell
+    routes.sort_by(|(one, _), (other, _)| other.len().cmp(&one.len()).then(one.cmp(other)));
+
+    // NOTE: whichever of the same prefixes would win is a guess, so better not to make it
+    if let Some(pair) = routes.windows(2).find(|pair| pair[0].0 == pair[1].0) {
+        return Err(format!("Duplicate route: {}", pair[0].0.escape_ascii()));
+    }
+
+    Ok(routes)
+}
+
+/// Parses a `;`-separated list of `host=status` defaults and `host/prefix=status` routes, into
+/// virtual hosts with lowercase names.
+fn parse_vhosts(value: &str) -> Result<Vec<(Vec<u8>, Vhost)>, String> {
+    let mut entries: Vec<(Vec<u8>, Option<u16>, String)> = Vec::new();
+
+    for entry in value
+        .split(';')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+    {
+        let (host, rest) = entry.split_at(entry.find(['/', '=']).unwrap_or(entry.len()));
+        let host = host.trim().to_ascii_lowercase().into_bytes();
+
+        if !is_host(&host) || rest.is_empty() {
+            return Err(format!("Invalid virtual host: {entry}"));
+        }
+
+        let index = match entries.iter().position(|(name, ..)| *name == host) {
+            Some(index) => index,
+            None => {
+                entries.push((host, None, String::new()));
+                entries.len() - 1
+            }
+        };
+        let (name, code, routes) = &mut entries[index];
+
+        // NOTE: the routes of a host are gathered up, so they are parsed and checked as ROUTES are
+        match rest.strip_prefix('=') {
+            Some(value) => {
+                if code
+                    .replace(parse_status(value.trim(), "virtual host")?)
+                    .is_some()
+                {
+                    return Err(format!("Duplicate virtual host: {}", name.escape_ascii()));
+                }
+            }
+            None => {
+                routes.push_str(rest);
+                routes.push(';');
+            }
+        }
+    }
+
+    entries
+        .into_iter()
+        .map(|(host, code, routes)| {
+            let vhost = Vhost {
+                default: code.map(ResponseMessage::from_code),
+                routes: parse_routes(&routes)?,
+            };
+
+            Ok((host, vhost))
+        })
+        .collect()
+}
+
+/// Represents the responses of a virtual host, i.e. of requests with a matching Host header.
+pub struct Vhost {
+    /// The response to unmatched paths, replacing the global default one if set.
+    pub default: Option<ResponseMessage<'static>>,
+    /// The routes, taking precedence over the global ones.
+    pub routes: Vec<(Vec<u8>, ResponseMessage<'static>)>,
+}
+
+/// Represents the runtime configuration, read at startup and on reload.
+pub struct Config {
+    pub addr: SocketAddr,
+    pub unix: Option<PathBuf>,
+    pub reuse_addr: bool,
+    pub reuse_port: bool,
+    pub workers: usize,
+    pub backlog: i32,
+    pub bind_retries: u32,
+    pub health: Vec<u8>,
+    pub health_json: bool,
+    pub liveness: Vec<u8>,
+    pub readiness: Vec<u8>,
+    pub server: Option<Vec<u8>>,
+    pub extra_headers: Vec<Vec<u8>>,
+    pub cors_origin: Option<Vec<u8>>,
+    pub read_timeout: Duration,
+    pub keepalive: Duration,
+    pub keepalive_max: usize,
+    pub shutdown: Duration,
+    pub watchdog: Option<Duration>,
+    pub watchdog_exit: bool,
+    pub max_uri_bytes: usize,
+    pub max_header_bytes: usize,
+    pub strict_line_endings: bool,
+    pub read_buffer_bytes: usize,
+    pub max_body_bytes: u64,
+    pub max_connections: usize,
+    pub rate_limit: Option<Rate>,
+    pub per_ip_limit: Option<Rate>,
+    pub nodelay: bool,
+    pub tcp_keepalive: Option<Duration>,
+    pub default: ResponseMessage<'static>,
+    pub root: Option<ResponseMessage<'static>>,
+    pub routes: Vec<(Vec<u8>, ResponseMessage<'static>)>,
+    pub vhosts: Vec<(Vec<u8>, Vhost)>,
+    pub strict_host: bool,
+    pub normalize_path: bool,
+    pub force_https: bool,
+    pub retry_after: Option<Vec<u8>>,
+    pub response_delay: Duration,
+    pub fault_rate: f64,
+    pub fault_health: bool,
+    pub compress: bool,
+    pub access_log: bool,
+    pub log_format: Format,
+    pub trust_proxy: bool,
+    pub proxy_protocol: bool,
+    pub log_level: Level,
+    pub log_errors: bool,
+    pub metrics: Option<Vec<u8>>,
+    pub stats: Option<Vec<u8>>,
+    pub echo: bool,
+    pub allow_trace: bool,
+    pub admin: Option<Vec<u8>>,
+    pub admin_token: Option<Vec<u8>>,
+    pub tls: Option<TlsAcceptor>,
+}
+
+impl Config {
+    /// Creates a new Config from the environment and an optional file, falling back to defaults.
+    pub fn from_env() -> Result<Config, String> {
+        Config::from_source(&Source::new()?)
+    }
+
+    /// Creates a new Config from a given Source, falling back to defaults.
+    fn from_source(source: &Source) -> Result<Config, String> {
+        let mut config = Config::default();
+
+        let port: u16 = match source.var("PORT") {
+            Some(value) => value.parse().map_err(|_| "Invalid port")?,
+            None => config.addr.port(),
+        };
+
+        // NOTE: brackets are tolerated so `[::]` works the same as `::`
+        let ip: IpAddr = match source.var("BIND") {
+            Some(value) => value
+                .trim_matches(['[', ']'])
+                .parse()
+                .map_err(|_| "Invalid bind address")?,
+            None => config.addr.ip(),
+        };
+
+        config.addr = SocketAddr::new(ip, port);
+
+        // NOTE: takes precedence over the TCP address, which therefore can stay as is
+        if let Some(value) = source.var("UNIX_SOCKET") {
+            config.unix = (!value.is_empty()).then(|| PathBuf::from(value));
+        }
+
+        if let Some(value) = source.var("REUSE_ADDR") {
+            config.reuse_addr = parse_flag(&value).ok_or("Invalid reuse address flag")?;
+        }
+
+        if let Some(value) = source.var("REUSE_PORT") {
+            config.reuse_port = parse_flag(&value).ok_or("Invalid reuse port flag")?;
+        }
+
+        if let Some(value) = source.var("WORKERS") {
+            config.workers = match value.parse() {
+                Ok(0) | Err(_) => return Err("Invalid workers".into()),
+                Ok(count) => count,
+            };
+        }
+
+        if let Some(value) = source.var("LISTEN_BACKLOG") {
+            config.backlog = match value.parse() {
+                Ok(length @ 1..) => length,
+                _ => return Err("Invalid listen backlog".into()),
+            };
+        }
+
+        if let Some(value) = source.var("BIND_RETRIES") {
+            config.bind_retries = value.parse().or(Err("Invalid bind retries"))?;
+        }
+
+        if let Some(value) = source.var("HEALTH_PATH") {
+            config.health = parse_path(value, "health")?;
+        }
+
+        if let Some(value) = source.var("HEALTH_JSON") {
+            config.health_json = parse_flag(&value).ok_or("Invalid health JSON flag")?;
+        }
+
+        if let Some(value) = source.var("LIVENESS_PATH") {
+            config.liveness = parse_path(value, "liveness")?;
+        }
+
+        if let Some(value) = source.var("READINESS_PATH") {
+            config.readiness = parse_path(value, "readiness")?;
+        }
+
+        if let Some(value) = source.var("SERVER_HEADER") {
+            if value.contains(['\r', '\n']) {
+                return Err("Invalid server header".into());
+            }
+            config.server = (!value.is_empty()).then(|| format!("Server: {value}").into_bytes());
+        }
+
+        if let Some(value) = source.var("EXTRA_HEADERS") {
+            config.extra_headers = parse_headers(&value)?;
+        }
+
+        if let Some(value) = source.var("CORS_ORIGIN") {
+            if value.contains(['\r', '\n']) {
+                return Err("Invalid CORS origin".into());
+            }
+            config.cors_origin = (!value.is_empty())
+                .then(|| format!("Access-Control-Allow-Origin: {value}").into_bytes());
+        }
+
+        if let Some(value) = source.var("READ_TIMEOUT") {
+            config.read_timeout = match value.parse() {
+                Ok(0) | Err(_) => return Err("Invalid read timeout".into()),
+                Ok(secs) => Duration::from_secs(secs),
+            };
+        }
+
+        if let Some(value) = source.var("KEEPALIVE_TIMEOUT") {
+            let secs = value.parse().map_err(|_| "Invalid keep-alive timeout")?;
+            config.keepalive = Duration::from_secs(secs);
+        }
+
+        if let Some(value) = source.var("KEEPALIVE_MAX") {
+            config.keepalive_max = match value.parse() {
+                Ok(0) | Err(_) => return Err("Invalid maximum keep-alive requests".into()),
+                Ok(count) => count,
+            };
+        }
+
+        if let Some(value) = source.var("SHUTDOWN_TIMEOUT") {
+            let secs = value.parse().map_err(|_| "Invalid shutdown timeout")?;
+            config.shutdown = Duration::from_secs(secs);
+        }
+
+        if let Some(value) = source.var("WATCHDOG_SECS") {
+            let secs: u64 = value.parse().map_err(|_| "Invalid watchdog interval")?;
+            config.watchdog = (secs > 0).then(|| Duration::from_secs(secs));
+        }
+
+        if let Some(value) = source.var("WATCHDOG_EXIT") {
+            config.watchdog_exit = parse_flag(&value).ok_or("Invalid watchdog exit flag")?;
+
+            // NOTE: quitting on a hang nobody watches for is more likely a mistake than a wish
+            if config.watchdog_exit && config.watchdog.is_none() {
+                return Err("Missing watchdog interval".into());
+            }
+        }
+
+        if let Some(value) = source.var("MAX_URI_BYTES") {
+            config.max_uri_bytes = match value.parse() {
+                Ok(bytes @ 1..=RequestMessage::PATH_LIMIT) => bytes,
+                _ => return Err("Invalid maximum URI bytes".into()),
+            };
+        }
+
+        if let Some(value) = source.var("MAX_HEADER_BYTES") {
+            config.max_header_bytes = match value.parse() {
+                Ok(0) | Err(_) => return Err("Invalid maximum header bytes".into()),
+                Ok(bytes) => bytes,
+            };
+        }
+
+        if let Some(value) = source.var("STRICT_LINE_ENDINGS") {
+            config.strict_line_endings =
+                parse_flag(&value).ok_or("Invalid strict line endings flag")?;
+        }
+
+        if let Some(value) = source.var("MAX_BODY_BYTES") {
+            config.max_body_bytes = value.parse().map_err(|_| "Invalid maximum body bytes")?;
+        }
+
+        if let Some(value) = source.var("READ_BUFFER_BYTES") {
+            // NOTE: every connection gets two buffers this large, so it is bound to something sane
+            config.read_buffer_bytes = match value.parse() {
+                Ok(bytes @ 1..=READ_BUFFER_MAX) => bytes,
+                _ => return Err("Invalid read buffer bytes".into()),
+            };
+        }
+
+        if let Some(value) = source.var("MAX_CONNECTIONS") {
+            config.max_connections = match value.parse() {
+                Ok(0) | Err(_) => return Err("Invalid maximum connections".into()),
+                Ok(count) => count,
+            };
+        }
+
+        config.rate_limit = parse_rate(source.var("RATE_LIMIT"), source.var("RATE_BURST"), "rate")?;
+
+        let (rate, burst) = (source.var("PER_IP_RATE"), source.var("PER_IP_BURST"));
+        config.per_ip_limit = parse_rate(rate, burst, "per-IP rate")?;
+
+        if let Some(value) = source.var("TCP_NODELAY") {
+            config.nodelay = parse_flag(&value).ok_or("Invalid TCP no-delay flag")?;
+        }
+
+        if let Some(value) = source.var("TCP_KEEPALIVE_SECS") {
+            config.tcp_keepalive = match value.parse() {
+                Ok(0) => None,
+                // NOTE: Linux refuses longer idle times and intervals, which would go unnoticed
+                Ok(secs @ 1..=32767) => Some(Duration::from_secs(secs)),
+                _ => return Err("Invalid TCP keep-alive interval".into()),
+            };
+        }
+
+        if let Some(value) = source.var("DEFAULT_STATUS") {
+            config.default = ResponseMessage::from_code(parse_status(&value, "default")?);
+        }
+
+        if let Some(value) = source.var("BODY_404") {
+            let content_type = match source.var("BODY_404_TYPE") {
+                Some(value) => Cow::Owned(parse_type(&value, "404")?),
+                None => Cow::Borrowed(TYPE_HTML),
+            };
+            set_body(&mut config.default, read_body(value)?, content_type)?;
+        } else if source.var("BODY_404_TYPE").is_some() {
+            // NOTE: a type without a body is more likely a mistake than a wish for no body
+            return Err("Missing 404 body".into());
+        }
+
+        if let Some(value) = source.var("ROOT_STATUS") {
+            config.root = Some(ResponseMessage::from_code(parse_status(&value, "root")?));
+        }
+
+        // NOTE: a root body alone is most likely meant to be a friendly 200
+        if let Some(value) = source.var("ROOT_BODY") {
+            let root = config.root.get_or_insert(ResponseMessage::from_code(200));
+            set_body(root, read_body(value)?, Cow::Borrowed(TYPE_HTML))?;
+        }
+
+        if let Some(value) = source.var("ROUTES") {
+            config.routes = parse_routes(&value)?;
+        }
+
+        if let Some(value) = source.var("VHOSTS") {
+            config.vhosts = parse_vhosts(&value)?;
+        }
+
+        if let Some(value) = source.var("STRICT_HOST") {
+            config.strict_host = parse_flag(&value).ok_or("Invalid strict host flag")?;
+        }
+
+        // NOTE: no host could ever match, so every request would be misdirected
+        if config.strict_host && config.vhosts.is_empty() {
+            return Err("Missing virtual hosts".into());
+        }
+
+        if let Some(value) = source.var("NORMALIZE_PATH") {
+            config.normalize_path = parse_flag(&value).ok_or("Invalid normalize path flag")?;
+        }
+
+        if let Some(value) = source.var("FORCE_HTTPS") {
+            config.force_https = parse_flag(&value).ok_or("Invalid force HTTPS flag")?;
+        }
+
+        if let Some(value) = source.var("RETRY_AFTER") {
+            let secs: u64 = value.parse().map_err(|_| "Invalid retry after")?;
+            config.retry_after = (secs > 0).then(|| format!("Retry-After: {secs}").into_bytes());
+        }
+
+        if let Some(value) = source.var("RESPONSE_DELAY_MS") {
+            let millis = value.parse().map_err(|_| "Invalid response delay")?;
+            config.response_delay = Duration::from_millis(millis);
+        }
+
+        if let Some(value) = source.var("FAULT_RATE") {
+            config.fault_rate = match value.parse() {
+                Ok(rate) if (0.0..=1.0).contains(&rate) => rate,
+                _ => return Err("Invalid fault rate".into()),
+            };
+        }
+
+        if let Some(value) = source.var("FAULT_INCLUDE_HEALTH") {
+            config.fault_health = parse_flag(&value).ok_or("Invalid fault include health flag")?;
+        }
+
+        if let Some(value) = source.var("COMPRESS") {
+            config.compress = parse_flag(&value).ok_or("Invalid compress flag")?;
+        }
+
+        if let Some(value) = source.var("ACCESS_LOG") {
+            config.access_log = parse_flag(&value).ok_or("Invalid access log flag")?;
+        }
+
+        if let Some(value) = source.var("LOG_FORMAT") {
+            config.log_format = Format::parse(&value).ok_or("Invalid log format")?;
+        }
+
+        if let Some(value) = source.var("TRUST_PROXY") {
+            config.trust_proxy = parse_flag(&value).ok_or("Invalid trust proxy flag")?;
+        }
+
+        if let Some(value) = source.var("PROXY_PROTOCOL") {
+            config.proxy_protocol = parse_flag(&value).ok_or("Invalid PROXY protocol flag")?;
+        }
+
+        if let Some(value) = source.var("LOG_LEVEL") {
+            config.log_level = Level::parse(&value).ok_or("Invalid log level")?;
+        }
+
+        // NOTE: debug logs all errors anyway, this way just them can be had at any level
+        if let Some(value) = source.var("LOG_ERRORS") {
+            config.log_errors = parse_flag(&value).ok_or("Invalid log errors flag")?;
+        }
+        config.log_errors |= config.log_level >= Level::Debug;
+
+        if let Some(value) = source.var("METRICS_PATH") {
+            if !value.is_empty() && !value.starts_with('/') {
+                return Err("Invalid metrics path".into());
+            }
+            config.metrics = (!value.is_empty()).then(|| value.into_bytes());
+        }
+
+        let stats = match source.var("STATS") {
+            Some(value) => parse_flag(&value).ok_or("Invalid stats flag")?,
+            None => false,
+        };
+        let stats_path = match source.var("STATS_PATH") {
+            // NOTE: a path without the flag is more likely a mistake than a wish for no stats
+            Some(_) if !stats => return Err("Missing stats flag".into()),
+            Some(value) => parse_path(value, "stats")?,
+            None => b"/stats".to_vec(),
+        };
+        config.stats = stats.then_some(stats_path);
+
+        if let Some(value) = source.var("ECHO") {
+            config.echo = parse_flag(&value).ok_or("Invalid echo flag")?;
+        }
+
+        if let Some(value) = source.var("ALLOW_TRACE") {
+            config.allow_trace = parse_flag(&value).ok_or("Invalid trace flag")?;
+        }
+
+        if let Some(value) = source.var("ADMIN_PATH") {
+            config.admin = match value.as_str() {
+                "" => None,
+                _ => Some(parse_path(value, "admin")?),
+            };
+        }
+
+        if let Some(value) = source.var("ADMIN_TOKEN") {
+            config.admin_token = (!value.is_empty()).then(|| value.into_bytes());
+        }
+
+        // NOTE: an admin endpoint anyone can call is more likely a mistake than a wish
+        if config.admin.is_some() && config.admin_token.is_none() {
+            return Err("Missing admin token".into());
+        }
+
+        // NOTE: a half-configured TLS is more likely a mistake than a wish for plaintext
+        config.tls = match (source.var("TLS_CERT"), source.var("TLS_KEY")) {
+            (Some(cert), Some(key)) => Some(tls::acceptor(Path::new(&cert), Path::new(&key))?),
+            (Some(_), None) => return Err("Missing TLS key".into()),
+            (None, Some(_)) => return Err("Missing TLS certificate".into()),
+            (None, None) => None,
+        };
+
+        Ok(config)
+    }
+
+    /// Summarizes the effective Config, one `name: value` line per concern, for people to check.
+    pub fn summary(&self) -> String {
+        let status = |response: &ResponseMessage| match response.body.len() {
+            0 => format!("{}", response.code),
+            bytes => format!("{} ({bytes} bytes)", response.code),
+        };
+        let path = |path: &Option<Vec<u8>>| match path {
+            Some(path) => path.escape_ascii().to_string(),
+            None => "off".into(),
+        };
+        let rate = |rate: &Option<Rate>| match rate {
+            Some(rate) => format!("{}/s, burst {}", rate.per_second, rate.burst),
+            None => "off".into(),
+        };
+
+        let listen = match &self.unix {
+            Some(path) => path.display().to_string(),
+            None => self.addr.to_string(),
+        };
+        let routes: Vec<String> = self
+            .routes
+            .iter()
+            .map(|(prefix, response)| format!("{}={}", prefix.escape_ascii(), response.code))
+            .collect();
+        let vhosts: Vec<String> = self
+            .vhosts
+            .iter()
+            .map(|(host, vhost)| {
+                let default = vhost.default.as_ref().map_or(self.default.code, |r| r.code);
+                format!(
+                    "{}={default} ({} routes)",
+                    host.escape_ascii(),
+                    vhost.routes.len()
+                )
+            })
+            .collect();
+        let list = |items: Vec<String>| {
+            if items.is_empty() {
+                "none".to_string()
+            } else {
+                items.join(", ")
+            }
+        };
+
+        [
+            match self.workers {
+                1 => format!("listen: {listen}"),
+                count => format!("listen: {listen} ({count} workers)"),
+            },
+            format!("tls: {}", if self.tls.is_some() { "on" } else { "off" }),
+            format!("default: {}", status(&self.default)),
+            format!(
+                "root: {}",
+                self.root.as_ref().map_or("default".into(), status)
+            ),
+            format!("routes: {}", list(routes)),
+            format!("vhosts: {}", list(vhosts)),
+            format!(
+                "health: {}, {}, {}",
+                self.health.escape_ascii(),
+                self.liveness.escape_ascii(),
+                self.readiness.escape_ascii()
+            ),
+            format!("metrics: {}", path(&self.metrics)),
+            format!("stats: {}", path(&self.stats)),
+            format!("admin: {}", path(&self.admin)),
+            format!("max_connections: {}", self.max_connections),
+            format!("rate_limit: {}", rate(&self.rate_limit)),
+            format!("per_ip_limit: {}", rate(&self.per_ip_limit)),
+        ]
+        .map(|line| line + "\n")
+        .concat()
+    }
+}
+
+/// Represents a Config shared between tasks, which can be swapped at runtime.
+pub struct Shared(RwLock<Arc<Config>>);
+
+impl Shared {
+    /// Creates a new Shared from a given Config.
+    pub fn new(config: Config) -> Shared {
+        Shared(RwLock::new(Arc::new(config)))
+    }
+
+    /// Returns the current Config, which stays the same for as long as it is held.
+    pub fn load(&self) -> Arc<Config> {
+        self.0
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Replaces the current Config with a given one.
+    pub fn store(&self, config: Config) {
+        *self.0.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(config);
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 8080),
+            unix: None,
+            reuse_addr: true,
+            reuse_port: false,
+            workers: 1,
+            backlog: 1024,
+            bind_retries: 0,
+            health: b"/healthz".to_vec(),
+            health_json: false,
+            liveness: b"/livez".to_vec(),
+            readiness: b"/readyz".to_vec(),
+            server: Some(b"Server: 404-container".to_vec()),
+            extra_headers: Vec::new(),
+            cors_origin: None,
+            read_timeout: Duration::from_secs(10),
+            keepalive: Duration::from_secs(5),
+            keepalive_max: 100,
+            shutdown: Duration::from_secs(10),
+            watchdog: None,
+            watchdog_exit: false,
+            max_uri_bytes: RequestMessage::PATH_LIMIT,
+            max_header_bytes: RequestMessage::HEADER_LIMIT,
+            strict_line_endings: false,
+            read_buffer_bytes: 4096,
+            max_body_bytes: 1024 * 1024,
+            max_connections: 1024,
+            rate_limit: None,
+            per_ip_limit: None,
+            nodelay: true,
+            tcp_keepalive: None,
+            default: RESP_404,
+            root: None,
+            routes: Vec::new(),
+            vhosts: Vec::new(),
+            strict_host: false,
+            normalize_path: false,
+            force_https: false,
+            retry_after: Some(b"Retry-After: 5".to_vec()),
+            response_delay: Duration::ZERO,
+            fault_rate: 0.0,
+            fault_health: false,
+            compress: false,
+            access_log: false,
+            log_format: Format::Text,
+            trust_proxy: false,
+            proxy_protocol: false,
+            log_level: Level::Info,
+            log_errors: false,
+            metrics: Some(b"/metrics".to_vec()),
+            stats: None,
+            echo: false,
+            allow_trace: false,
+            admin: None,
+            admin_token: None,
+            tls: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_source_parse() {
+        let source = Source::parse("max_connections = 8\naccess_log = true\nbind = '::1'").unwrap();
+        let other = Source::parse("fault_rate = 0.25").unwrap();
+
+        assert!(other.file["FAULT_RATE"] == "0.25");
+        assert!(source.file["MAX_CONNECTIONS"] == "8");
+        assert!(source.file["ACCESS_LOG"] == "true");
+        assert!(source.file["BIND"] == "::1");
+    }
+
+    #[test]
+    fn test_source_parse_with_unknown_key() {
+        let result = Source::parse("helth_path = '/healthz'");
+
+        assert!(result.is_err_and(|err| err == "Unknown configuration key: helth_path"));
+    }
+
+    #[test]
+    fn test_source_parse_with_uppercase_key() {
+        let result = Source::parse("HEALTH_PATH = '/healthz'");
+
+        assert!(result.is_err_and(|err| err == "Unknown configuration key: HEALTH_PATH"));
+    }
+
+    #[test]
+    fn test_source_parse_with_table() {
+        let result = Source::parse("[extra_headers]\nX-Frame-Options = 'DENY'");
+
+        assert!(result.is_err_and(|err| err == "Invalid configuration value: extra_headers"));
+    }
+
+    #[test]
+    fn test_source_parse_with_invalid_syntax() {
+        let result = Source::parse("max_connections = ");
+
+        assert!(result.is_err_and(|err| err.starts_with("Cannot parse configuration file")));
+    }
+
+    #[test]
+    fn test_parse_env_file() {
+        let text = "# local settings\n\
+                    PORT=1080\n\
+                    \n\
+                    export BIND = 127.0.0.1 # loopback only\n\
+                    BODY_404=\"<h1>Nope</h1>\\n\\\"really\\\"\" # comment\n\
+                    SERVER_HEADER='a \\n b # c'\n\
+                    EXTRA_HEADERS=X-Id: a#b\n\
+                    CORS_ORIGIN=\n";
+
+        let result = parse_env_file(text).unwrap();
+
+        assert!(result.len() == 6);
+        assert!(result["PORT"] == "1080");
+        assert!(result["BIND"] == "127.0.0.1");
+        assert!(result["BODY_404"] == "<h1>Nope</h1>\n\"really\"");
+        assert!(result["SERVER_HEADER"] == "a \\n b # c");
+        assert!(result["EXTRA_HEADERS"] == "X-Id: a#b");
+        assert!(result["CORS_ORIGIN"].is_empty());
+    }
+
+    #[test]
+    fn test_parse_env_file_invalid() {
+        for (text, line) in [
+            ("PORT", 1),
+            ("# ok\n=1080", 2),
+            ("MY PORT=1080", 1),
+            ("PORT=\"1080", 1),
+            ("PORT=\"10\\t80\"", 1),
+            ("PORT='1080' 1", 1),
+        ] {
+            let result = parse_env_file(text);
+
+            assert!(result.is_err_and(|err| err == format!("Invalid .env line {line}")));
+        }
+    }
+
+    #[test]
+    fn test_source_var_precedence() {
+        let pairs = |pairs: &[(&str, &str)]| {
+            pairs
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect()
+        };
+        let source = Source {
+            env_file: pairs(&[("PATH", "/env-file"), ("READINESS_PATH", "/env-file")]),
+            file: pairs(&[("READINESS_PATH", "/file"), ("HEALTH_PATH", "/file")]),
+        };
+
+        assert!(source.var("PATH") == env::var("PATH").ok());
+        assert!(source.var("READINESS_PATH").as_deref() == Some("/env-file"));
+        assert!(source.var("HEALTH_PATH").as_deref() == Some("/file"));
+    }
+
+    #[test]
+    fn test_from_source() {
+        let source = Source::parse("max_connections = 8\ndefault_status = 410").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(config.max_connections == 8);
+        assert!(config.default.code == 410);
+    }
+
+    #[test]
+    fn test_from_source_with_keepalive_max() {
+        let source = Source::parse("keepalive_max = 0").unwrap();
+
+        let result = Config::from_source(&source);
+
+        assert!(result.is_err_and(|err| err == "Invalid maximum keep-alive requests"));
+    }
+
+    #[test]
+    fn test_from_source_with_watchdog_exit_alone() {
+        for text in [
+            "watchdog_exit = true",
+            "watchdog_secs = 0\nwatchdog_exit = true",
+        ] {
+            let source = Source::parse(text).unwrap();
+
+            let result = Config::from_source(&source);
+
+            assert!(result.is_err_and(|err| err == "Missing watchdog interval"));
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_watchdog() {
+        let source = Source::parse("watchdog_secs = 30\nwatchdog_exit = true").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(config.watchdog == Some(Duration::from_secs(30)));
+        assert!(config.watchdog_exit);
+    }
+
+    #[test]
+    fn test_from_source_with_listen_backlog() {
+        for (value, expected) in [
+            ("4096", Some(4096)),
+            ("0", None),
+            ("-1", None),
+            ("lots", None),
+        ] {
+            let source = Source::parse(&format!("listen_backlog = '{value}'")).unwrap();
+
+            let result = Config::from_source(&source).map(|config| config.backlog);
+
+            assert!(result.ok() == expected);
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_tcp_keepalive() {
+        for (value, expected) in [
+            ("30", Some(Some(Duration::from_secs(30)))),
+            ("0", Some(None)),
+            ("32767", Some(Some(Duration::from_secs(32767)))),
+            ("32768", None),
+            ("18446744073709551615", None),
+            ("-1", None),
+            ("soon", None),
+        ] {
+            let source = Source::parse(&format!("tcp_keepalive_secs = '{value}'")).unwrap();
+
+            let result = Config::from_source(&source).map(|config| config.tcp_keepalive);
+
+            assert!(result.ok() == expected);
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_read_buffer_bytes() {
+        for (value, expected) in [
+            ("1", Some(1)),
+            ("1048576", Some(1048576)),
+            ("0", None),
+            ("1048577", None),
+            ("1000000000000", None),
+        ] {
+            let source = Source::parse(&format!("read_buffer_bytes = '{value}'")).unwrap();
+
+            let result = Config::from_source(&source).map(|config| config.read_buffer_bytes);
+
+            assert!(result.ok() == expected);
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_bind_retries() {
+        for (value, expected) in [("5", Some(5)), ("0", Some(0)), ("-1", None), ("a", None)] {
+            let source = Source::parse(&format!("bind_retries = '{value}'")).unwrap();
+
+            let result = Config::from_source(&source).map(|config| config.bind_retries);
+
+            assert!(result.ok() == expected);
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_rate_limit() {
+        let source = Source::parse("rate_limit = 10").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(config
+            .rate_limit
+            .is_some_and(|rate| rate.per_second == 10 && rate.burst == 10));
+    }
+
+    #[test]
+    fn test_from_source_with_per_ip_rate() {
+        let source = Source::parse("per_ip_rate = 5\nper_ip_burst = 0").unwrap();
+
+        let result = Config::from_source(&source);
+
+        assert!(result.is_err_and(|err| err == "Invalid per-IP rate burst"));
+    }
+
+    #[test]
+    fn test_from_source_with_per_ip_burst_alone() {
+        for text in ["per_ip_burst = 5", "per_ip_rate = 0\nper_ip_burst = 5"] {
+            let source = Source::parse(text).unwrap();
+
+            let result = Config::from_source(&source);
+
+            assert!(result.is_err_and(|err| err == "Missing per-IP rate limit"));
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_rate_burst() {
+        let source = Source::parse("rate_limit = 10\nrate_burst = 50").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(config.rate_limit.is_some_and(|rate| rate.burst == 50));
+    }
+
+    #[test]
+    fn test_from_source_with_invalid_rate() {
+        for (text, expected) in [
+            ("rate_burst = 5", "Missing rate limit"),
+            ("rate_limit = 0\nrate_burst = 5", "Missing rate limit"),
+            ("rate_limit = 1000000001", "Invalid rate limit"),
+            ("rate_limit = -1", "Invalid rate limit"),
+        ] {
+            let source = Source::parse(text).unwrap();
+
+            let result = Config::from_source(&source);
+
+            assert!(result.is_err_and(|err| err == expected));
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_rate_limit_at_maximum() {
+        let source = Source::parse("rate_limit = 1000000000").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(config
+            .rate_limit
+            .is_some_and(|rate| rate.per_second == 1_000_000_000));
+    }
+
+    #[test]
+    fn test_from_source_with_body() {
+        let source = Source::parse("body_404 = '<h1>Nope</h1>'").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(config.default.content_type.as_deref() == Some(TYPE_HTML));
+        assert!(config.default.body.as_ref() == b"<h1>Nope</h1>");
+        assert!(config.default.etag.as_deref() == Some(b"\"d-558560d9\"".as_slice()));
+    }
+
+    #[test]
+    fn test_from_source_with_body_and_bodiless_status() {
+        for (text, expected) in [
+            (
+                "default_status = 204\nbody_404 = 'Nope'",
+                "Body not allowed with status 204",
+            ),
+            (
+                "default_status = 304\nbody_404 = 'Nope'",
+                "Body not allowed with status 304",
+            ),
+            (
+                "root_status = 103\nroot_body = 'Hi'",
+                "Body not allowed with status 103",
+            ),
+        ] {
+            let source = Source::parse(text).unwrap();
+
+            let result = Config::from_source(&source);
+
+            assert!(result.is_err_and(|err| err == expected));
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_body_type() {
+        let source = Source::parse("body_404 = '{}'\nbody_404_type = 'application/json'").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+
+        assert!(
+            config.default.content_type.as_deref()
+                == Some(b"Content-Type: application/json".as_slice())
+        );
+    }
+
+    #[test]
+    fn test_from_source_with_invalid_body_type() {
+        for value in [
+            "''",
+            "\"text/html\\r\\nX-Injected: 1\"",
+            "\"text/html\\u0000\"",
+            "\"text/html;\\tcharset=utf-8\"",
+            "\"text/html\\u007f\"",
+        ] {
+            let text = format!("body_404 = '{{}}'\nbody_404_type = {value}");
+            let source = Source::parse(&text).unwrap();
+
+            let result = Config::from_source(&source);
+
+            assert!(result.is_err_and(|err| err == "Invalid 404 body type"));
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_body_type_alone() {
+        let source = Source::parse("body_404_type = 'application/json'").unwrap();
+
+        let result = Config::from_source(&source);
+
+        assert!(result.is_err_and(|err| err == "Missing 404 body"));
+    }
+
+    #[test]
+    fn test_from_source_with_stats() {
+        for (text, expected) in [
+            ("", None),
+            ("stats = 1", Some(b"/stats".as_slice())),
+            ("stats = 1\nstats_path = '/_stats'", Some(b"/_stats")),
+        ] {
+            let source = Source::parse(text).unwrap();
+
+            let config = Config::from_source(&source).unwrap();
+
+            assert!(config.stats.as_deref() == expected);
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_stats_path_alone() {
+        for text in [
+            "stats_path = '/_stats'",
+            "stats = 0\nstats_path = '/_stats'",
+        ] {
+            let source = Source::parse(text).unwrap();
+
+            let result = Config::from_source(&source);
+
+            assert!(result.is_err_and(|err| err == "Missing stats flag"));
+        }
+    }
+
+    #[test]
+    fn test_summary() {
+        let source = Source::parse(
+            "port = 1080\nbody_404 = 'Nope'\nroutes = '/api=503'\nvhosts = 'a.example=410'\n\
+             stats = 1\nrate_limit = 10",
+        )
+        .unwrap();
+
+        let result = Config::from_source(&source).unwrap().summary();
+
+        assert!(result.starts_with("listen: 0.0.0.0:1080\ntls: off\n"));
+        assert!(result.contains("\ndefault: 404 (4 bytes)\nroot: default\n"));
+        assert!(result.contains("\nroutes: /api=503\nvhosts: a.example=410 (0 routes)\n"));
+        assert!(result.contains("\nhealth: /healthz, /livez, /readyz\n"));
+        assert!(result.contains("\nmetrics: /metrics\nstats: /stats\nadmin: off\n"));
+        assert!(result.contains("\nrate_limit: 10/s, burst 10\nper_ip_limit: off\n"));
+    }
+
+    #[test]
+    fn test_from_source_with_invalid_value() {
+        let source = Source::parse("max_connections = 0").unwrap();
+
+        let result = Config::from_source(&source);
+
+        assert!(result.is_err_and(|err| err == "Invalid maximum connections"));
+    }
+
+    #[test]
+    fn test_from_source_with_line_break_in_cors_origin() {
+        let source = Source::parse("cors_origin = \"*\\r\\nSet-Cookie: a=b\"").unwrap();
+
+        let result = Config::from_source(&source);
+
+        assert!(result.is_err_and(|err| err == "Invalid CORS origin"));
+    }
+
+    #[test]
+    fn test_from_source_with_max_uri_bytes_over_limit() {
+        let source = Source::parse("max_uri_bytes = 65537").unwrap();
+
+        let result = Config::from_source(&source);
+
+        assert!(result.is_err_and(|err| err == "Invalid maximum URI bytes"));
+    }
+
+    #[test]
+    fn test_shared() {
+        let shared = Shared::new(Config::default());
+        let held = shared.load();
+
+        shared.store(Config {
+            keepalive: Duration::ZERO,
+            ..Config::default()
+        });
+
+        assert!(held.keepalive == Duration::from_secs(5));
+        assert!(shared.load().keepalive.is_zero());
+    }
+
+    #[test]
+    fn test_shared_with_body() {
+        let source = Source::parse("body_404 = '<h1>Nope</h1>'").unwrap();
+        let shared = Shared::new(Config::from_source(&source).unwrap());
+        let held = Arc::downgrade(&shared.load());
+
+        assert!(matches!(shared.load().default.body, Cow::Owned(_)));
+        assert!(matches!(shared.load().default.etag, Some(Cow::Owned(_))));
+
+        shared.store(Config::default());
+
+        // NOTE: the body is owned by the Config, so nothing of it is left once that is replaced
+        assert!(held.upgrade().is_none());
+        assert!(shared.load().default.body.is_empty());
+    }
+
+    #[test]
+    fn test_parse_routes() {
+        let result = parse_routes("/api=503; /api/v2 = 200;/ok=200;").unwrap();
+
+        let prefixes: Vec<&[u8]> = result.iter().map(|(prefix, _)| prefix.as_slice()).collect();
+        let codes: Vec<u16> = result.iter().map(|(_, response)| response.code).collect();
+
+        assert!(prefixes == [b"/api/v2".as_slice(), b"/api", b"/ok"]);
+        assert!(codes == [200, 503, 200]);
+    }
+
+    #[test]
+    fn test_parse_routes_invalid() {
+        let cases = [
+            ("/api", "Invalid route: /api"),
+            ("api=503", "Invalid route path"),
+            ("=503", "Empty route path"),
+            ("/api=5O3", "Invalid route status"),
+            ("/api=600", "Invalid route status"),
+            ("/api=503;/web=200;/api=200", "Duplicate route: /api"),
+        ];
+
+        for (value, error) in cases {
+            assert!(parse_routes(value).is_err_and(|err| err == error));
+        }
+    }
+
+    #[test]
+    fn test_parse_vhosts() {
+        let result = parse_vhosts("A.example=503; a.example/api=200;b.example:8080/=410").unwrap();
+
+        let hosts: Vec<&[u8]> = result.iter().map(|(host, _)| host.as_slice()).collect();
+        let (_, a) = &result[0];
+        let (_, b) = &result[1];
+
+        assert!(hosts == [b"a.example".as_slice(), b"b.example:8080"]);
+        assert!(a
+            .default
+            .as_ref()
+            .is_some_and(|response| response.code == 503));
+        assert!(a.routes.len() == 1 && a.routes[0].0 == b"/api" && a.routes[0].1.code == 200);
+        assert!(b.default.is_none());
+        assert!(b.routes.len() == 1 && b.routes[0].1.code == 410);
+    }
+
+    #[test]
+    fn test_parse_vhosts_invalid() {
+        let cases = [
+            ("a.example", "Invalid virtual host: a.example"),
+            ("=503", "Invalid virtual host: =503"),
+            ("a example=503", "Invalid virtual host: a example=503"),
+            ("a.example=600", "Invalid virtual host status"),
+            ("a.example/api", "Invalid route: /api"),
+            (
+                "a.example=503;A.example=200",
+                "Duplicate virtual host: a.example",
+            ),
+            (
+                "a.example/api=503;a.example/api=200",
+                "Duplicate route: /api",
+            ),
+        ];
+
+        for (value, error) in cases {
+            assert!(parse_vhosts(value).is_err_and(|err| err == error));
+        }
+    }
+
+    #[test]
+    fn test_from_source_with_strict_host() {
+        let source = Source::parse("vhosts = 'a.example=200'\nstrict_host = 1").unwrap();
+        let missing = Source::parse("strict_host = 1").unwrap();
+
+        let config = Config::from_source(&source).unwrap();
+        let result = Config::from_source(&missing);
+
+        assert!(config.strict_host);
+        assert!(result.is_err_and(|err| err == "Missing virtual hosts"));
+    }
+
+    #[test]
+    fn test_parse_headers() {
+        let result = parse_headers("X-Frame-Options: DENY; Cache-Control:no-store;").unwrap();
+
+        assert!(
+            result
+                == [
+                    b"X-Frame-Options: DENY".as_slice(),
+                    b"Cache-Control: no-store"
+                ]
+        );
+    }
+
+    #[test]
+    fn test_parse_headers_without_colon() {
+        let result = parse_headers("X-Frame-Options DENY");
+
+        assert!(result.is_err_and(|err| err == "Invalid extra header: X-Frame-Options DENY"));
+    }
+
+    #[test]
+    fn test_parse_headers_with_invalid_name() {
+        let result = parse_headers("X-Frame Options: DENY");
+
+        assert!(result.is_err_and(|err| err == "Invalid extra header name: X-Frame Options"));
+    }
+
+    #[test]
+    fn test_parse_headers_with_line_break() {
+        let result = parse_headers("X-Test: a\r\nSet-Cookie: b");
+
+        assert!(result.is_err());
+    }
+}
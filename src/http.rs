@@ -1,6 +1,14 @@
-use std::io::Read;
+use std::borrow::Cow;
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::sync::atomic::Ordering;
+use std::sync::LazyLock;
 
-use crate::SEP;
+use crate::config::{Config, Vhost};
+use crate::metrics;
+use crate::state::{READY, SHUTDOWN};
+use crate::utils::random;
+use crate::{CRLF, SEP};
 
 type Version<'v> = &'v [u8];
 type Method<'m> = &'m [u8];
@@ -10,36 +18,426 @@ const VERSIONS: [Version; 2] = [b"HTTP/1.0", b"HTTP/1.1"];
 const METHODS: [Method; 8] = [
     b"GET", b"HEAD", b"POST", b"PUT", b"DELETE", b"OPTIONS", b"PATCH", b"TRACE",
 ];
+const REFUSED: [Method; 1] = [b"CONNECT"];
 
 const VERSION_LIMIT: usize = 8;
 const METHOD_LIMIT: usize = 7;
 const PATH_LIMIT: usize = u16::MAX as usize + 1;
+const HEADER_LIMIT: usize = 8 * 1024;
 
+const ECHO_PATH: &[u8] = b"/debug/echo";
+
+pub const RESP_100: ResponseMessage = ResponseMessage::with_status(100, b"Continue");
+const RESP_204: ResponseMessage = ResponseMessage::with_status(204, b"No Content");
+pub const RESP_400: ResponseMessage = ResponseMessage::with_status(400, b"Bad Request");
 const RESP_200: ResponseMessage = ResponseMessage::with_status(200, b"OK");
-const RESP_400: ResponseMessage = ResponseMessage::with_status(400, b"Bad Request");
-const RESP_404: ResponseMessage = ResponseMessage::with_status(404, b"Not Found");
+const RESP_304: ResponseMessage = ResponseMessage::with_status(304, b"Not Modified");
+const RESP_308: ResponseMessage = ResponseMessage::with_status(308, b"Permanent Redirect");
+const RESP_401: ResponseMessage = ResponseMessage::with_status(401, b"Unauthorized");
+pub const RESP_404: ResponseMessage = ResponseMessage::with_status(404, b"Not Found");
 const RESP_405: ResponseMessage = ResponseMessage::with_status(405, b"Method Not Allowed");
-const RESP_414: ResponseMessage = ResponseMessage::with_status(414, b"URI Too Long");
+pub const RESP_408: ResponseMessage = ResponseMessage::with_status(408, b"Request Timeout");
+pub const RESP_413: ResponseMessage = ResponseMessage::with_status(413, b"Content Too Large");
+pub const RESP_414: ResponseMessage = ResponseMessage::with_status(414, b"URI Too Long");
+const RESP_421: ResponseMessage = ResponseMessage::with_status(421, b"Misdirected Request");
+pub const RESP_429: ResponseMessage = ResponseMessage::with_status(429, b"Too Many Requests");
+pub const RESP_431: ResponseMessage =
+    ResponseMessage::with_status(431, b"Request Header Fields Too Large");
+const RESP_500: ResponseMessage = ResponseMessage::with_status(500, b"Internal Server Error");
+pub const RESP_501: ResponseMessage = ResponseMessage::with_status(501, b"Not Implemented");
+const RESP_503: ResponseMessage = ResponseMessage::with_status(503, b"Service Unavailable");
 const RESP_505: ResponseMessage = ResponseMessage::with_status(505, b"HTTP Version Not Supported");
 
+// NOTE: derived from METHODS, so it never goes out of sync; TRACE is left out unless allowed
+static ALLOW: LazyLock<Vec<u8>> =
+    LazyLock::new(|| [b"Allow: ", &METHODS.join(&b", "[..])[..]].concat());
+static ALLOW_UNTRACED: LazyLock<Vec<u8>> = LazyLock::new(|| {
+    let methods: Vec<Method> = METHODS.into_iter().filter(|m| *m != b"TRACE").collect();
+    [b"Allow: ", &methods.join(&b", "[..])[..]].concat()
+});
+static HEADERS_ALLOW: LazyLock<[&[u8]; 1]> = LazyLock::new(|| [ALLOW.as_slice()]);
+static HEADERS_ALLOW_UNTRACED: LazyLock<[&[u8]; 1]> = LazyLock::new(|| [ALLOW_UNTRACED.as_slice()]);
+
+const HEADERS_TRACE: [&[u8]; 1] = [b"Content-Type: message/http"];
+// NOTE: credentials are never reflected, so a script cannot read them from an echo (XST)
+const UNTRACED: [&str; 3] = ["Authorization", "Proxy-Authorization", "Cookie"];
+
+const HEADERS_JSON: [&[u8]; 1] = [b"Content-Type: application/json"];
+const HEALTH_OK: &[u8] = br#"{"status":"ok"}"#;
+const HEALTH_DRAINING: &[u8] = br#"{"status":"draining"}"#;
+
+// NOTE: only reading is allowed cross-origin, the admin endpoints are not for browsers
+const HEADERS_PREFLIGHT: [&[u8]; 3] = [
+    b"Access-Control-Allow-Methods: GET, HEAD",
+    b"Access-Control-Allow-Headers: X-Request-Id",
+    b"Access-Control-Max-Age: 600",
+];
+
+type Header<'h> = (&'h [u8], &'h [u8]);
+
+/// Checks if a given byte is allowed in a token, i.e. a `tchar` of RFC 9110.
+pub fn is_tchar(byte: &u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(byte)
+}
+
+/// Checks if a given host is a plain `host[:port]` one, so it is safe to put into a header.
+pub fn is_host(host: &[u8]) -> bool {
+    !host.is_empty()
+        && host
+            .iter()
+            .all(|char| char.is_ascii_alphanumeric() || b"-._:[]".contains(char))
+}
+
+/// Returns a given `host[:port]` without the port, if any, minding the colons of IPv6 ones.
+fn strip_port(host: &[u8]) -> &[u8] {
+    match host.iter().rposition(|char| char == &b':') {
+        Some(index) if !host.ends_with(b"]") => &host[..index],
+        _ => host,
+    }
+}
+
+/// Returns a given path if it has no control characters, which could forge logs or headers.
+fn sanitize_path(path: &[u8]) -> Option<&[u8]> {
+    (!path.iter().any(u8::is_ascii_control)).then_some(path)
+}
+
+/// Parses an `X-Forwarded-For` entry into an IP address, if it is a valid one.
+fn parse_forwarded(entry: &[u8]) -> Option<IpAddr> {
+    let entry = std::str::from_utf8(entry.trim_ascii()).ok()?;
+
+    // NOTE: some proxies append a port, which is of no use here
+    entry
+        .parse()
+        .ok()
+        .or_else(|| entry.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Decodes percent-encoded octets of a given path, borrowing it as is if there are none.
+///
+/// Returns `None` if a percent sign is not followed by two hex digits.
+fn decode_path(path: &[u8]) -> Option<Cow<'_, [u8]>> {
+    if !path.contains(&b'%') {
+        return Some(Cow::Borrowed(path));
+    }
+
+    let hex = |digit: &u8| char::from(*digit).to_digit(16);
+    let mut decoded = Vec::with_capacity(path.len());
+    let mut bytes = path.iter();
+
+    while let Some(byte) = bytes.next() {
+        if byte == &b'%' {
+            let (high, low) = (hex(bytes.next()?)?, hex(bytes.next()?)?);
+            decoded.push((high * 16 + low) as u8);
+        } else {
+            decoded.push(*byte);
+        }
+    }
+
+    Some(Cow::Owned(decoded))
+}
+
+/// Returns a readiness to set if a given decoded path asks for an admin action, i.e. to drain
+/// or undrain.
+fn admin_action(path: &[u8], config: &Config) -> Option<bool> {
+    match path.strip_prefix(config.admin.as_deref()?)? {
+        b"/drain" => Some(false),
+        b"/undrain" => Some(true),
+        _ => None,
+    }
+}
+
+/// Normalizes a given path, collapsing repeated slashes, resolving `.` and `..` segments, and
+/// stripping a trailing slash, with a query left as is, e.g. `//a/./b/?q` becomes `/a/b?q`.
+fn normalize_path(path: &[u8]) -> Cow<'_, [u8]> {
+    // NOTE: anything but an origin-form path, e.g. `*`, is not a path to begin with
+    if !path.starts_with(b"/") {
+        return Cow::Borrowed(path);
+    }
+
+    let index = path
+        .iter()
+        .position(|char| char == &b'?')
+        .unwrap_or(path.len());
+    let (path, query) = path.split_at(index);
+    let mut segments: Vec<&[u8]> = Vec::new();
+
+    for segment in path.split(|char| char == &b'/') {
+        match segment {
+            b"" | b"." => {}
+            b".." => {
+                segments.pop(); // there is nothing above the root
+            }
+            segment => segments.push(segment),
+        }
+    }
+
+    let mut normalized = Vec::with_capacity(path.len() + query.len());
+    for segment in segments {
+        normalized.push(b'/');
+        normalized.extend_from_slice(segment);
+    }
+    if normalized.is_empty() {
+        normalized.push(b'/');
+    }
+    normalized.extend_from_slice(query);
+
+    Cow::Owned(normalized)
+}
+
+/// Returns the Allow header according to a given Config, i.e. with TRACE only when allowed.
+fn allow(config: &Config) -> &'static [&'static [u8]] {
+    if config.allow_trace {
+        HEADERS_ALLOW.as_slice()
+    } else {
+        HEADERS_ALLOW_UNTRACED.as_slice()
+    }
+}
+
+/// Returns a response of the longest route prefix a given decoded path falls under, if any.
+///
+/// A prefix matches whole segments only, e.g. `/api` matches `/api/v1` and `/api?q`, not `/apis`.
+fn route<'c>(
+    path: &[u8],
+    routes: &'c [(Vec<u8>, ResponseMessage<'static>)],
+) -> Option<&'c ResponseMessage<'static>> {
+    // NOTE: routes are sorted from the longest prefix, so the first match is the longest one
+    routes.iter().find_map(|(prefix, response)| {
+        let rest = path.strip_prefix(prefix.as_slice())?;
+        let whole = rest.is_empty() || prefix.ends_with(b"/") || b"/?".contains(&rest[0]);
+
+        whole.then_some(response)
+    })
+}
+
+/// Represents a request body, as framed by the request headers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Body {
+    /// The body has a known length, zero if there is none.
+    Sized(u64),
+    /// The body is transfer-coded, e.g. chunked, so its length is unknown upfront.
+    Coded,
+    /// The body length is invalid or ambiguous.
+    Malformed,
+}
+
 /// Represents a simplified HTTP request message.
 pub struct RequestMessage<'a> {
     pub method: Method<'a>,
     pub path: Path<'a>,
     pub http: Version<'a>,
+    pub headers: Vec<Header<'a>>,
 }
 
 impl<'a> RequestMessage<'a> {
-    pub const LIMIT: usize = METHOD_LIMIT + PATH_LIMIT + VERSION_LIMIT + 2;
+    pub const PATH_LIMIT: usize = PATH_LIMIT;
+    pub const HEADER_LIMIT: usize = HEADER_LIMIT;
+
+    /// Returns the request line limit for a given path limit, i.e. with room for the rest of it.
+    pub const fn line_limit(path_limit: usize) -> usize {
+        METHOD_LIMIT + SEP.len() + path_limit + SEP.len() + VERSION_LIMIT + CRLF.len()
+    }
+
+    /// Returns a value of the first header with a given name, compared case-insensitively.
+    pub fn header(&self, name: &str) -> Option<&[u8]> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name.as_bytes()))
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns the framing of the RequestMessage body, according to its headers.
+    pub fn body(&self) -> Body {
+        if self.header("Transfer-Encoding").is_some() {
+            return Body::Coded;
+        }
+
+        let mut lengths = self
+            .headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(b"Content-Length"))
+            .map(|(_, value)| *value);
+
+        // NOTE: repeated lengths must all agree, or there is no telling which one is right
+        match lengths.next() {
+            None => Body::Sized(0),
+            Some(length) if lengths.any(|other| other != length) => Body::Malformed,
+            Some(length) if length.is_empty() || !length.iter().all(u8::is_ascii_digit) => {
+                Body::Malformed
+            }
+            Some(length) => std::str::from_utf8(length)
+                .ok()
+                .and_then(|length| length.parse().ok())
+                .map_or(Body::Malformed, Body::Sized),
+        }
+    }
+
+    /// Returns the request ID a client or a proxy has sent, if it is a sane one.
+    pub fn request_id(&self) -> Option<&[u8]> {
+        self.header("X-Request-Id").filter(|value| {
+            (1..=128).contains(&value.len()) && value.iter().all(u8::is_ascii_graphic)
+        })
+    }
+
+    /// Returns the client IP address a proxy has forwarded the RequestMessage for, if it is valid.
+    ///
+    /// The left-most address is the client one, the rest are the proxies in between.
+    pub fn forwarded_for(&self) -> Option<IpAddr> {
+        let value = self.header("X-Forwarded-For")?;
+
+        parse_forwarded(value.split(|char| char == &b',').next()?)
+    }
+
+    /// Returns the IP address the nearest proxy has forwarded the RequestMessage from, if it is
+    /// valid.
+    ///
+    /// It is the right-most address of the last header, i.e. the one the proxy has appended, so
+    /// unlike the left-most one it is not the client to make up.
+    pub fn forwarded_last(&self) -> Option<IpAddr> {
+        let value = self
+            .headers
+            .iter()
+            .rev()
+            .find(|(key, _)| key.eq_ignore_ascii_case(b"X-Forwarded-For"))
+            .map(|(_, value)| *value)?;
+
+        parse_forwarded(value.rsplit(|char| char == &b',').next()?)
+    }
+
+    /// Checks if the client waits for a go-ahead before sending the body (`100-continue`).
+    pub fn is_continue_expected(&self) -> bool {
+        self.http == VERSIONS[1]
+            && self
+                .header("Expect")
+                .is_some_and(|value| value.eq_ignore_ascii_case(b"100-continue"))
+    }
+
+    /// Checks if the client accepts a gzip-encoded response, i.e. lists it without a zero quality.
+    pub fn is_gzip_accepted(&self) -> bool {
+        self.header("Accept-Encoding").is_some_and(|value| {
+            value.split(|char| char == &b',').any(|item| {
+                let mut params = item.split(|char| char == &b';').map(<[u8]>::trim_ascii);
+                let coding = params.next().unwrap_or_default();
+
+                // NOTE: `q=0` means "not acceptable", however many zeros it is written with
+                let refused = params.any(|param| {
+                    param.len() > 2
+                        && param[..2].eq_ignore_ascii_case(b"q=")
+                        && param[2..].iter().all(|char| b"0.".contains(char))
+                });
+
+                (coding.eq_ignore_ascii_case(b"gzip") || coding.eq_ignore_ascii_case(b"x-gzip"))
+                    && !refused
+            })
+        })
+    }
+
+    /// Checks if the Connection headers have a given token, compared case-insensitively.
+    fn has_connection_token(&self, token: &[u8]) -> bool {
+        // NOTE: repeated headers are the same as one with their values joined with commas
+        self.headers
+            .iter()
+            .filter(|(key, _)| key.eq_ignore_ascii_case(b"Connection"))
+            .flat_map(|(_, value)| value.split(|char| char == &b','))
+            .any(|item| item.trim_ascii().eq_ignore_ascii_case(token))
+    }
+
+    /// Checks if the connection may persist after the RequestMessage is responded to.
+    ///
+    /// HTTP/1.1 connections persist unless closed explicitly, and HTTP/1.0 ones only when asked.
+    pub fn is_persistent(&self) -> bool {
+        if self.has_connection_token(b"close") {
+            false
+        } else if self.http == VERSIONS[1] {
+            true
+        } else {
+            self.http == VERSIONS[0] && self.has_connection_token(b"keep-alive")
+        }
+    }
+
+    /// Checks if the method is a well-formed token, supported or not.
+    fn is_method_well_formed(&self) -> bool {
+        !self.method.is_empty() && self.method.iter().all(is_tchar)
+    }
 
     /// Checks if the method is supported.
     fn is_method_valid(&self) -> bool {
         METHODS.contains(&self.method)
     }
 
-    /// Checks if the path is valid.
+    /// Checks if the method is a known one, even if not supported.
+    fn is_method_known(&self) -> bool {
+        self.is_method_valid() || REFUSED.contains(&self.method)
+    }
+
+    /// Checks if the path is valid, i.e. in origin-form, in absolute-form, in asterisk-form for
+    /// OPTIONS, or in authority-form for CONNECT.
     fn is_path_valid(&self) -> bool {
         self.path.starts_with(b"/")
+            || self.absolute_path().is_some()
+            || self.is_asterisk()
+            || self.is_authority()
+    }
+
+    /// Returns the path part of an absolute-form target, e.g. `http://host/path`, if it is one.
+    ///
+    /// The part may be empty, or start with a query, as there is nothing but a host before it.
+    fn absolute_path(&self) -> Option<&[u8]> {
+        let index = self.path.iter().position(|char| char == &b':')?;
+        let (scheme, rest) = self.path.split_at(index);
+
+        if !scheme.eq_ignore_ascii_case(b"http") && !scheme.eq_ignore_ascii_case(b"https") {
+            return None;
+        }
+
+        let rest = rest.strip_prefix(b"://")?;
+        let index = rest
+            .iter()
+            .position(|char| b"/?#".contains(char))
+            .unwrap_or(rest.len());
+        let (host, path) = rest.split_at(index);
+
+        is_host(host).then_some(path)
+    }
+
+    /// Returns the path as routed, i.e. in origin-form if it is in absolute-form, or as is.
+    pub fn origin(&self) -> Cow<'_, [u8]> {
+        match self.absolute_path() {
+            Some(path) if path.starts_with(b"/") => Cow::Borrowed(path),
+            Some(path) => Cow::Owned([b"/", path].concat()),
+            None => Cow::Borrowed(self.path),
+        }
+    }
+
+    /// Checks if the RequestMessage asks to tunnel to a `host:port` (`CONNECT`).
+    fn is_authority(&self) -> bool {
+        let (host, port) = match self.path.iter().rposition(|char| char == &b':') {
+            Some(index) => (&self.path[..index], &self.path[index + 1..]),
+            None => return false,
+        };
+
+        self.method == b"CONNECT"
+            && !host.is_empty()
+            && !host.contains(&b'/')
+            && !port.is_empty()
+            && port.iter().all(u8::is_ascii_digit)
+    }
+
+    /// Checks if the RequestMessage asks about the server as a whole (`OPTIONS *`).
+    fn is_asterisk(&self) -> bool {
+        self.method == b"OPTIONS" && self.path == b"*"
+    }
+
+    /// Checks if the RequestMessage is an HTTP/2 connection preface, i.e. `PRI * HTTP/2.0`.
+    fn is_h2_preface(&self) -> bool {
+        self.method == b"PRI" && self.path == b"*" && self.http == b"HTTP/2.0"
+    }
+
+    /// Checks if the HTTP version is well-formed, i.e. `HTTP/` followed by `DIGIT.DIGIT`.
+    fn is_http_well_formed(&self) -> bool {
+        match self.http.strip_prefix(b"HTTP/") {
+            Some([major, b'.', minor]) => major.is_ascii_digit() && minor.is_ascii_digit(),
+            _ => false,
+        }
     }
 
     /// Checks if the HTTP version is supported.
@@ -47,6 +445,181 @@ impl<'a> RequestMessage<'a> {
         VERSIONS.contains(&self.http)
     }
 
+    /// Checks if the RequestMessage bears the admin token as a bearer one.
+    fn is_authorized(&self, config: &Config) -> bool {
+        let (token, value) = match (&config.admin_token, self.header("Authorization")) {
+            (Some(token), Some(value)) => (token, value),
+            _ => return false,
+        };
+
+        // NOTE: compares every byte, so the time taken tells nothing about a prefix match
+        value.len() == token.len() + 7
+            && value[..7].eq_ignore_ascii_case(b"Bearer ")
+            && value[7..]
+                .iter()
+                .zip(token)
+                .fold(0, |diff, (left, right)| diff | (left ^ right))
+                == 0
+    }
+
+    /// Checks if the method only retrieves a resource (i.e. GET or HEAD).
+    fn is_readonly(&self) -> bool {
+        self.method == b"GET" || self.method == b"HEAD"
+    }
+
+    /// Checks if the client has a body with a given entity tag already, as told by If-None-Match.
+    ///
+    /// Tags are compared weakly, i.e. regardless of a `W/` prefix (RFC 9110, section 13.1.2).
+    fn is_not_modified(&self, etag: &[u8]) -> bool {
+        self.is_readonly()
+            && self.header("If-None-Match").is_some_and(|value| {
+                value.split(|char| char == &b',').any(|tag| {
+                    let tag = tag.trim_ascii();
+                    tag == b"*" || tag.strip_prefix(b"W/").unwrap_or(tag) == etag
+                })
+            })
+    }
+
+    /// Returns a ResponseMessage telling the client its body is still the current one, if it has
+    /// the one of a given ResponseMessage already.
+    ///
+    /// Only a successful response is ever replaced, as preconditions are ignored for any other
+    /// (RFC 9110, section 13.2.1).
+    pub fn not_modified<'r>(&self, response: &ResponseMessage<'r>) -> Option<ResponseMessage<'r>> {
+        let etag = response.etag.as_deref()?;
+
+        (response.code / 100 == 2 && self.is_not_modified(etag)).then(|| ResponseMessage {
+            etag: response.etag.clone(),
+            ..RESP_304
+        })
+    }
+
+    /// Returns the `Location` header redirecting the RequestMessage to HTTPS, if it should be.
+    ///
+    /// It should be only if configured so, and a terminating proxy says it came over plain HTTP.
+    pub fn redirect(&self, config: &Config) -> Option<Vec<u8>> {
+        let proto = self.header("X-Forwarded-Proto")?;
+        let host = self.header("Host").filter(|host| is_host(host))?;
+
+        // NOTE: the first value is the one the client used, whatever the proxies in between did
+        let proto = proto.split(|char| char == &b',').next()?.trim_ascii();
+
+        let path = self.origin();
+
+        if !config.force_https || !proto.eq_ignore_ascii_case(b"http") || !path.starts_with(b"/") {
+            return None;
+        }
+
+        // NOTE: a plain HTTP port is of no use for HTTPS, so the default one is implied instead
+        Some([b"Location: https://", strip_port(host), &path].concat())
+    }
+
+    /// Returns the virtual host the RequestMessage is for, matching its Host header as is
+    /// first, and without a port then, both case-insensitively.
+    fn vhost<'c>(&self, config: &'c Config) -> Option<&'c Vhost> {
+        let host = self.header("Host")?.trim_ascii();
+
+        [host, strip_port(host)].into_iter().find_map(|host| {
+            config
+                .vhosts
+                .iter()
+                .find_map(|(name, vhost)| name.eq_ignore_ascii_case(host).then_some(vhost))
+        })
+    }
+
+    /// Checks if the RequestMessage is for the stats, which are not access logged, as whoever
+    /// watches them would mostly see themselves watching.
+    pub fn is_stats(&self, config: &Config) -> bool {
+        config.stats.as_deref() == Some(self.matched_path(config).as_ref())
+    }
+
+    /// Returns the origin-form path of the RequestMessage as it is matched against configured
+    /// ones, i.e. decoded, sanitized, and normalized if a given Config says so.
+    ///
+    /// Routes are matched decoded, so no encoding sneaks past them, or into them; a path failing
+    /// to decode or sanitize is empty, which a valid one never is.
+    fn matched_path(&self, config: &Config) -> Cow<'_, [u8]> {
+        let origin = self.origin();
+        let decoded = decode_path(&origin).map(|decoded| match decoded {
+            Cow::Owned(decoded) => Some(decoded),
+            Cow::Borrowed(_) => None,
+        });
+        let decoded = match decoded {
+            None => return Cow::Borrowed(b""),
+            Some(Some(decoded)) => Cow::Owned(decoded),
+            Some(None) => origin,
+        };
+
+        if sanitize_path(&decoded).is_none() {
+            return Cow::Borrowed(b"");
+        }
+        if config.normalize_path {
+            if let Cow::Owned(normalized) = normalize_path(&decoded) {
+                return Cow::Owned(normalized);
+            }
+        }
+
+        decoded
+    }
+
+    /// Checks if the RequestMessage is a health check, i.e. of liveness or readiness.
+    pub fn is_probe(&self, config: &Config) -> bool {
+        let path = self.matched_path(config);
+
+        [&config.health, &config.liveness, &config.readiness]
+            .iter()
+            .any(|probe| probe.as_slice() == path.as_ref())
+    }
+
+    /// Checks if the RequestMessage is to fail on purpose, as a configured fraction of them is.
+    fn is_faulty(&self, config: &Config) -> bool {
+        config.fault_rate > 0.0
+            && (config.fault_health || !self.is_probe(config))
+            && random() < config.fault_rate
+    }
+
+    /// Describes the RequestMessage as parsed, one field per line, with anything unprintable
+    /// (and backslashes) escaped, so nothing sent can pass for something else.
+    fn describe(&self) -> String {
+        let mut text = format!(
+            "method: {}\npath: {}\nversion: {}\n",
+            self.method.escape_ascii(),
+            self.path.escape_ascii(),
+            self.http.escape_ascii(),
+        );
+
+        for (name, value) in &self.headers {
+            text.push_str(&format!(
+                "header: {}: {}\n",
+                name.escape_ascii(),
+                value.escape_ascii()
+            ));
+        }
+
+        text
+    }
+
+    /// Reconstructs the RequestMessage as received, for TRACE to reflect it as `message/http`.
+    fn trace(&self) -> Vec<u8> {
+        let mut bytes = [self.method, SEP, self.path, SEP, self.http, CRLF].concat();
+
+        for (name, value) in &self.headers {
+            if !UNTRACED
+                .iter()
+                .any(|untraced| name.eq_ignore_ascii_case(untraced.as_bytes()))
+            {
+                bytes.extend_from_slice(&[name, b": ".as_slice(), value, CRLF].concat());
+            }
+        }
+
+        bytes
+    }
+
+    /// Checks if the RequestMessage is a CORS preflight one, i.e. asks what a browser may send.
+    fn is_preflight(&self) -> bool {
+        self.method == b"OPTIONS" && self.header("Access-Control-Request-Method").is_some()
+    }
+
     /// Checks if the RequestMessage is empty.
     fn is_empty(&self) -> bool {
         self.method.is_empty() && self.path.is_empty() && self.http.is_empty()
@@ -57,22 +630,125 @@ impl<'a> RequestMessage<'a> {
         self.method.is_ascii() && self.path.is_ascii() && self.http.is_ascii()
     }
 
-    /// Returns an appropriate ResponseMessage.
-    pub fn response(&self) -> &ResponseMessage {
-        if self.is_empty() || !self.is_ascii() || !self.is_path_valid() {
-            &RESP_400
+    /// Returns an appropriate ResponseMessage according to a given Config.
+    pub fn response<'c>(&self, config: &'c Config) -> ResponseMessage<'c> {
+        let matched = self.matched_path(config);
+        let path = matched.as_ref();
+        let vhost = self.vhost(config);
+
+        // NOTE: a client with prior knowledge of HTTP/2 is told plainly it is not spoken here
+        if self.is_h2_preface() {
+            RESP_505
+        } else if self.is_empty()
+            || !self.is_ascii()
+            || !self.is_method_well_formed()
+            || !self.is_path_valid()
+            || path.is_empty()
+        {
+            RESP_400
+        } else if !self.is_method_known() {
+            RESP_501
         } else if !self.is_method_valid() {
-            &RESP_405
+            // NOTE: CONNECT is a known method, refused since there is nothing to tunnel to
+            ResponseMessage {
+                headers: allow(config),
+                ..RESP_405
+            }
+        } else if self.path.len() > config.max_uri_bytes {
+            RESP_414
         } else if !self.is_http_valid() {
-            if self.http.is_empty() {
-                &RESP_414
+            if self.is_http_well_formed() {
+                RESP_505
+            } else {
+                RESP_400
+            }
+        } else if self.is_faulty(config) {
+            // NOTE: a deliberate failure for testing retries, kept off the health checks
+            RESP_500
+        } else if self.method == b"TRACE" {
+            // NOTE: reflecting requests is a known risk (XST), so it is refused unless allowed
+            if config.allow_trace {
+                ResponseMessage {
+                    headers: HEADERS_TRACE.as_slice(),
+                    body: Cow::Owned(self.trace()),
+                    ..RESP_200
+                }
+            } else {
+                ResponseMessage {
+                    headers: allow(config),
+                    ..RESP_405
+                }
+            }
+        } else if self.is_asterisk() {
+            ResponseMessage {
+                headers: allow(config),
+                ..RESP_204
+            }
+        } else if config.cors_origin.is_some() && self.is_preflight() {
+            ResponseMessage {
+                headers: HEADERS_PREFLIGHT.as_slice(),
+                ..RESP_204
+            }
+        } else if path == config.health || path == config.liveness {
+            health(true, config)
+        } else if path == config.readiness {
+            health(READY.load(Ordering::Relaxed), config)
+        } else if self.redirect(config).is_some() {
+            // NOTE: health checks come straight from a load balancer, so they are never redirected
+            RESP_308
+        } else if self.is_readonly() && config.metrics.as_deref() == Some(path) {
+            ResponseMessage {
+                headers: &[b"Content-Type: text/plain; version=0.0.4; charset=utf-8"],
+                body: Cow::Owned(metrics::render().into_bytes()),
+                ..RESP_200
+            }
+        } else if self.is_readonly() && config.stats.as_deref() == Some(path) {
+            ResponseMessage {
+                headers: &[b"Content-Type: text/plain; charset=utf-8"],
+                body: Cow::Owned(metrics::stats().into_bytes()),
+                ..RESP_200
+            }
+        } else if config.echo && path == ECHO_PATH {
+            ResponseMessage {
+                headers: &[b"Content-Type: text/plain; charset=utf-8"],
+                body: Cow::Owned(self.describe().into_bytes()),
+                ..RESP_200
+            }
+        } else if let Some(ready) = admin_action(path, config) {
+            if self.method != b"POST" {
+                ResponseMessage {
+                    headers: &[b"Allow: POST"],
+                    ..RESP_405
+                }
+            } else if !self.is_authorized(config) {
+                ResponseMessage {
+                    headers: &[b"WWW-Authenticate: Bearer"],
+                    ..RESP_401
+                }
             } else {
-                &RESP_505
+                // NOTE: there is no coming back once shutting down
+                READY.store(
+                    ready && !SHUTDOWN.load(Ordering::Relaxed),
+                    Ordering::Relaxed,
+                );
+                RESP_204
             }
-        } else if self.path == b"/healthz" {
-            &RESP_200 // I would prefer 204 though
+        } else if config.strict_host && vhost.is_none() {
+            // NOTE: e.g. a shared TLS front end sent it to the wrong backend, which is worth telling
+            RESP_421
+        } else if let Some(response) = vhost
+            .and_then(|vhost| route(path, &vhost.routes))
+            .or_else(|| route(path, &config.routes))
+        {
+            response.as_borrowed()
+        } else if let (b"/", Some(root)) = (path, &config.root) {
+            root.as_borrowed()
         } else {
-            &RESP_404
+            // NOTE: an unmatched host, or a matched one without a default, gets the global one
+            vhost
+                .and_then(|vhost| vhost.default.as_ref())
+                .unwrap_or(&config.default)
+                .as_borrowed()
         }
     }
 }
@@ -81,29 +757,136 @@ impl<'a> From<&'a [u8]> for RequestMessage<'a> {
     fn from(value: &'a [u8]) -> Self {
         let (mut method, mut path, mut http): (&[u8], &[u8], &[u8]) = (b"", b"", b"");
 
-        value
+        // NOTE: everything after a CR is ignored, so a bare one ends a line as well
+        let mut lines = value
+            .split(|char| char == &CRLF[1])
+            .map(|line| line.split(|char| char == &CRLF[0]).next().unwrap_or(line));
+
+        lines
+            .next()
+            .unwrap_or_default()
             .splitn(3, |char| char == &SEP[0])
-            .zip([METHOD_LIMIT, PATH_LIMIT, VERSION_LIMIT])
-            .map(|(source, limit)| {
-                if source.len() > limit {
-                    &source[..limit]
-                } else {
-                    source
-                }
-            })
+            // NOTE: every field is kept whole, as the reader has already bounded the line, so
+            // nothing cut short passes for something else, e.g. `OPTIONSX` for `OPTIONS`
             .zip([method.by_ref(), path.by_ref(), http.by_ref()])
             .for_each(|(source, target)| *target = source);
 
-        RequestMessage { method, path, http }
+        let headers = lines
+            .take_while(|line| !line.is_empty())
+            .filter_map(|line| {
+                let index = line.iter().position(|char| char == &b':')?;
+                Some((&line[..index], line[index + 1..].trim_ascii()))
+            })
+            .collect();
+
+        RequestMessage {
+            method,
+            path,
+            http,
+            headers,
+        }
+    }
+}
+
+/// Returns a health check ResponseMessage, bodiless, or with a JSON body if configured so.
+///
+/// A body needs a status allowing it, so a healthy JSON response is 200 instead of 204.
+fn health(healthy: bool, config: &Config) -> ResponseMessage<'static> {
+    let (response, body) = match (healthy, config.health_json) {
+        (true, false) => return RESP_204,
+        (false, false) => return RESP_503,
+        (true, true) => (RESP_200, HEALTH_OK),
+        (false, true) => (RESP_503, HEALTH_DRAINING),
+    };
+
+    ResponseMessage {
+        headers: &HEADERS_JSON,
+        body: Cow::Borrowed(body),
+        ..response
+    }
+}
+
+/// Returns a reason phrase for a given status code, as registered by IANA.
+pub const fn reason_phrase(code: u16) -> &'static [u8] {
+    match code {
+        100 => b"Continue",
+        101 => b"Switching Protocols",
+        102 => b"Processing",
+        103 => b"Early Hints",
+        200 => b"OK",
+        201 => b"Created",
+        202 => b"Accepted",
+        203 => b"Non-Authoritative Information",
+        204 => b"No Content",
+        205 => b"Reset Content",
+        206 => b"Partial Content",
+        207 => b"Multi-Status",
+        208 => b"Already Reported",
+        226 => b"IM Used",
+        300 => b"Multiple Choices",
+        301 => b"Moved Permanently",
+        302 => b"Found",
+        303 => b"See Other",
+        304 => b"Not Modified",
+        305 => b"Use Proxy",
+        307 => b"Temporary Redirect",
+        308 => b"Permanent Redirect",
+        400 => b"Bad Request",
+        401 => b"Unauthorized",
+        402 => b"Payment Required",
+        403 => b"Forbidden",
+        404 => b"Not Found",
+        405 => b"Method Not Allowed",
+        406 => b"Not Acceptable",
+        407 => b"Proxy Authentication Required",
+        408 => b"Request Timeout",
+        409 => b"Conflict",
+        410 => b"Gone",
+        411 => b"Length Required",
+        412 => b"Precondition Failed",
+        413 => b"Content Too Large",
+        414 => b"URI Too Long",
+        415 => b"Unsupported Media Type",
+        416 => b"Range Not Satisfiable",
+        417 => b"Expectation Failed",
+        418 => b"I'm a teapot",
+        421 => b"Misdirected Request",
+        422 => b"Unprocessable Content",
+        423 => b"Locked",
+        424 => b"Failed Dependency",
+        425 => b"Too Early",
+        426 => b"Upgrade Required",
+        428 => b"Precondition Required",
+        429 => b"Too Many Requests",
+        431 => b"Request Header Fields Too Large",
+        451 => b"Unavailable For Legal Reasons",
+        500 => b"Internal Server Error",
+        501 => b"Not Implemented",
+        502 => b"Bad Gateway",
+        503 => b"Service Unavailable",
+        504 => b"Gateway Timeout",
+        505 => b"HTTP Version Not Supported",
+        506 => b"Variant Also Negotiates",
+        507 => b"Insufficient Storage",
+        508 => b"Loop Detected",
+        510 => b"Not Extended",
+        511 => b"Network Authentication Required",
+        _ => b"Unknown",
     }
 }
 
 /// Represents a simplified HTTP (response) message.
+#[derive(Clone)]
 pub struct ResponseMessage<'a> {
     pub http: Version<'a>,
     pub code: u16,
     pub desc: &'a [u8],
-    pub headers: [&'a [u8]; 1],
+    pub headers: &'a [&'a [u8]],
+    pub body: Cow<'a, [u8]>,
+    /// The Content-Type header of a configured body, written after the other headers.
+    pub content_type: Option<Cow<'a, [u8]>>,
+    /// The quoted entity tag of a body that never changes, if it is one.
+    pub etag: Option<Cow<'a, [u8]>>,
 }
 
 impl<'a> ResponseMessage<'a> {
@@ -113,7 +896,78 @@ impl<'a> ResponseMessage<'a> {
             http: VERSIONS[1],
             code,
             desc,
-            headers: [b"Connection: close"],
+            headers: &[],
+            body: Cow::Borrowed(b""),
+            content_type: None,
+            etag: None,
+        }
+    }
+
+    /// Returns a ResponseMessage borrowing everything from this one, rather than cloning its body.
+    pub fn as_borrowed(&self) -> ResponseMessage<'_> {
+        ResponseMessage {
+            http: self.http,
+            code: self.code,
+            desc: self.desc,
+            headers: self.headers,
+            body: Cow::Borrowed(&self.body),
+            content_type: self.content_type.as_deref().map(Cow::Borrowed),
+            etag: self.etag.as_deref().map(Cow::Borrowed),
+        }
+    }
+
+    /// Checks if the ResponseMessage status forbids a body.
+    pub const fn is_bodiless(&self) -> bool {
+        // NOTE: 1xx and 204 responses must not carry Content-Length (RFC 7230, section 3.3.2),
+        // and a 304 one could only carry that of the response it stands for
+        self.code < 200 || self.code == 204 || self.code == 304
+    }
+
+    /// Creates a new ResponseMessage with a given status code and its reason phrase.
+    pub const fn from_code(code: u16) -> ResponseMessage<'a> {
+        ResponseMessage::with_status(code, reason_phrase(code))
+    }
+
+    /// Serializes the ResponseMessage head, followed by given extra headers, into bytes.
+    ///
+    /// The body is left out, so it is up to the caller whether to write it (e.g. not for HEAD).
+    pub fn to_bytes(&self, extra: &[&[u8]]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_head(&mut bytes, extra);
+
+        bytes
+    }
+
+    /// Appends the ResponseMessage head, followed by given extra headers, to a given buffer.
+    ///
+    /// Nothing is allocated if the buffer has enough room, e.g. when reused between responses.
+    pub fn write_head(&self, bytes: &mut Vec<u8>, extra: &[&[u8]]) {
+        self.write_fields(bytes);
+        for header in extra {
+            bytes.extend_from_slice(header);
+            bytes.extend_from_slice(CRLF);
+        }
+        bytes.extend_from_slice(CRLF);
+    }
+
+    /// Appends the ResponseMessage status line and headers to a given buffer, leaving the head
+    /// open for the caller to append more headers to, and to end.
+    pub fn write_fields(&self, bytes: &mut Vec<u8>) {
+        // NOTE: writing to a vector never fails, it is the formatting that is of use here
+        bytes.extend_from_slice(self.http);
+        bytes.extend_from_slice(SEP);
+        write!(bytes, "{}", self.code).ok();
+        bytes.extend_from_slice(SEP);
+        bytes.extend_from_slice(self.desc);
+        bytes.extend_from_slice(CRLF);
+
+        for header in self.headers.iter().chain(&self.content_type.as_deref()) {
+            bytes.extend_from_slice(header);
+            bytes.extend_from_slice(CRLF);
+        }
+        if !self.is_bodiless() {
+            write!(bytes, "Content-Length: {}", self.body.len()).ok();
+            bytes.extend_from_slice(CRLF);
         }
     }
 }
@@ -121,9 +975,157 @@ impl<'a> ResponseMessage<'a> {
 #[cfg(test)]
 mod tests {
     use std::any::{Any, TypeId};
+    use std::sync::{LazyLock, Mutex};
+    use std::time::Duration;
+
+    use proptest::prelude::*;
 
     use super::*;
 
+    // NOTE: responses borrow from the Config, which has to outlive them, and is cheap to share
+    static DEFAULT: LazyLock<Config> = LazyLock::new(Config::default);
+
+    // NOTE: readiness is global, so the tests relying on it must not run at the same time
+    static READINESS: Mutex<()> = Mutex::new(());
+
+    // NOTE: every status the default Config may respond with, whatever a request is
+    const CODES: [u16; 9] = [200, 204, 400, 404, 405, 414, 501, 503, 505];
+
+    #[test]
+    fn test_request_message_response_500() {
+        let config = Config {
+            fault_rate: 1.0,
+            ..Config::default()
+        };
+
+        for path in [b"/".as_slice(), b"/foo", b"/metrics"] {
+            let data = [b"GET ".as_slice(), path, b" HTTP/1.1\r\n\r\n"].concat();
+
+            let result = RequestMessage::from(data.as_slice()).response(&config);
+
+            assert!(result.code == RESP_500.code);
+            assert!(result.desc == RESP_500.desc);
+        }
+
+        let data = b"GET /healthz HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_204.code);
+    }
+
+    #[test]
+    fn test_request_message_response_500_with_health() {
+        let data = b"GET /healthz HTTP/1.1\r\n\r\n";
+        let config = Config {
+            fault_rate: 1.0,
+            fault_health: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_500.code);
+    }
+
+    #[test]
+    fn test_request_message_response_500_with_rate() {
+        let data = b"GET / HTTP/1.1\r\n\r\n";
+        let config = Config {
+            fault_rate: 0.5,
+            ..Config::default()
+        };
+
+        let request = RequestMessage::from(data.as_slice());
+        let faults = (0..1000)
+            .filter(|_| request.response(&config).code == RESP_500.code)
+            .count();
+
+        assert!((400..600).contains(&faults));
+    }
+
+    /// Generates request heads looking mostly valid, so they get past the first checks.
+    fn request_head() -> impl Strategy<Value = Vec<u8>> {
+        let method = prop_oneof![
+            prop::sample::select(METHODS.to_vec()).prop_map(<[u8]>::to_vec),
+            "[A-Z]{1,10}".prop_map(String::into_bytes),
+        ];
+        let path = prop_oneof![
+            "/[a-z0-9%/._?=-]{0,32}".prop_map(String::into_bytes),
+            "https?://[a-z.]{1,16}(/[a-z0-9/]{0,16})?".prop_map(String::into_bytes),
+            Just(b"*".to_vec()),
+            "[a-z.]{1,16}:[0-9]{1,5}".prop_map(String::into_bytes),
+        ];
+        let http = prop_oneof![
+            prop::sample::select(VERSIONS.to_vec()).prop_map(<[u8]>::to_vec),
+            "HTTP/[0-9].[0-9]".prop_map(String::into_bytes),
+            prop::collection::vec(any::<u8>(), 0..12),
+        ];
+        let headers = prop::collection::vec("[A-Za-z-]{1,16}: [ -~]{0,32}\r\n", 0..4);
+
+        (method, path, http, headers).prop_map(|(method, path, http, headers)| {
+            [
+                method,
+                b" ".to_vec(),
+                path,
+                b" ".to_vec(),
+                http,
+                b"\r\n".to_vec(),
+            ]
+            .concat()
+            .into_iter()
+            .chain(headers.concat().into_bytes())
+            .chain(*b"\r\n")
+            .collect()
+        })
+    }
+
+    /// Checks the invariants of parsing given bytes, and responding to them.
+    fn check_invariants(data: &[u8]) -> Result<(), TestCaseError> {
+        let request = RequestMessage::from(data);
+
+        prop_assert!(!request.method.contains(&SEP[0]) && !request.path.contains(&SEP[0]));
+        prop_assert!(!request.method.contains(&CRLF[1]) && !request.path.contains(&CRLF[1]));
+        prop_assert!(!request.http.contains(&CRLF[0]) && !request.http.contains(&CRLF[1]));
+
+        for (name, value) in &request.headers {
+            prop_assert!(!name.contains(&b':'));
+            prop_assert!(!value.contains(&CRLF[0]) && !value.contains(&CRLF[1]));
+        }
+
+        let response = request.response(&DEFAULT);
+
+        prop_assert!(
+            CODES.contains(&response.code),
+            "unexpected {}",
+            response.code
+        );
+        prop_assert!(request.method.len() <= METHOD_LIMIT || matches!(response.code, 400 | 501));
+        Ok(())
+    }
+
+    proptest! {
+        #[test]
+        fn test_request_message_from_arbitrary_bytes(
+            data in prop::collection::vec(any::<u8>(), 0..256)
+        ) {
+            check_invariants(&data)?;
+        }
+
+        #[test]
+        fn test_request_message_from_request_head(data in request_head()) {
+            check_invariants(&data)?;
+        }
+    }
+
+    fn admin_config() -> Config {
+        Config {
+            admin: Some(b"/admin".to_vec()),
+            admin_token: Some(b"secret".to_vec()),
+            ..Config::default()
+        }
+    }
+
     #[test]
     fn test_request_message_from() {
         let data = b"GET /test HTTP/1.1";
@@ -167,123 +1169,1429 @@ mod tests {
         let result = RequestMessage::from(data.as_slice());
 
         assert!(result.type_id() == TypeId::of::<RequestMessage>());
-        assert!(result.method == b"OPTIONS");
+        assert!(result.method == b"OPTIONSBUTLONGER");
         assert!(result.path == b"/test");
         assert!(result.http == b"HTTP/1.1");
+        assert!(result.response(&DEFAULT).code == RESP_501.code);
     }
 
     #[test]
-    fn test_request_message_response_200() {
-        let data = &RequestMessage {
-            method: b"GET",
-            path: b"/healthz",
-            http: b"HTTP/1.1",
-        };
+    fn test_request_message_from_with_headers() {
+        let data = b"GET /test HTTP/1.1\r\nHost: example.com\r\nX-Empty:\r\nbroken\r\nAccept:  */* \r\n\r\n";
 
-        let result = data.response();
+        let result = RequestMessage::from(data.as_slice());
 
-        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.type_id() == TypeId::of::<RequestMessage>());
+        assert!(result.method == b"GET");
+        assert!(result.path == b"/test");
+        assert!(result.http == b"HTTP/1.1");
+        assert!(result.headers.len() == 3);
+        assert!(result.header("host") == Some(b"example.com".as_slice()));
+        assert!(result.header("X-EMPTY") == Some(b"".as_slice()));
+        assert!(result.header("Accept") == Some(b"*/*".as_slice()));
+        assert!(result.header("Broken").is_none());
+    }
+
+    #[test]
+    fn test_request_message_from_stops_at_blank_line() {
+        let data = b"GET /test HTTP/1.1\r\nHost: example.com\r\n\r\nX-Body: nope";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.headers.len() == 1);
+        assert!(result.header("X-Body").is_none());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent() {
+        let data = b"GET / HTTP/1.1\r\nConnection: keep-alive\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent_with_close() {
+        let data = b"GET / HTTP/1.1\r\nconnection: Upgrade, Close\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent_with_repeated_header() {
+        let data = b"GET / HTTP/1.1\r\nConnection: keep-alive\r\nConnection: close\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent_with_http_1_0() {
+        let data = b"GET / HTTP/1.0\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent_with_http_1_0_keep_alive() {
+        let data = b"GET / HTTP/1.0\r\nConnection: Keep-Alive\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent_with_http_1_0_keep_alive_and_close() {
+        let data = b"GET / HTTP/1.0\r\nConnection: keep-alive, close\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_is_persistent_without_header() {
+        let data = b"GET / HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.is_persistent());
+    }
+
+    #[test]
+    fn test_request_message_body() {
+        let data = b"POST / HTTP/1.1\r\ncontent-length: 42\r\nContent-Length: 42\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.body() == Body::Sized(42));
+    }
+
+    #[test]
+    fn test_request_message_body_without_length() {
+        let result = RequestMessage::from(b"GET / HTTP/1.1\r\n\r\n".as_slice());
+
+        assert!(result.body() == Body::Sized(0));
+    }
+
+    #[test]
+    fn test_request_message_body_coded() {
+        let data = b"POST / HTTP/1.1\r\nContent-Length: 3\r\nTransfer-Encoding: chunked\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.body() == Body::Coded);
+    }
+
+    #[test]
+    fn test_request_message_body_malformed() {
+        for data in [
+            b"POST / HTTP/1.1\r\nContent-Length: 3\r\nContent-Length: 4\r\n\r\n".as_slice(),
+            b"POST / HTTP/1.1\r\nContent-Length: -3\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 3, 3\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length:\r\n\r\n",
+            b"POST / HTTP/1.1\r\nContent-Length: 99999999999999999999\r\n\r\n",
+        ] {
+            let result = RequestMessage::from(data);
+
+            assert!(result.body() == Body::Malformed);
+        }
+    }
+
+    #[test]
+    fn test_request_message_request_id() {
+        let data = b"GET / HTTP/1.1\r\nX-Request-Id: abc-123\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.request_id() == Some(b"abc-123".as_slice()));
+    }
+
+    #[test]
+    fn test_request_message_request_id_invalid() {
+        let long = [
+            b"GET / HTTP/1.1\r\nX-Request-Id: ".as_slice(),
+            &[b'a'; 129],
+            b"\r\n\r\n",
+        ]
+        .concat();
+
+        for data in [
+            b"GET / HTTP/1.1\r\nX-Request-Id: a b\r\n\r\n".as_slice(),
+            b"GET / HTTP/1.1\r\nX-Request-Id:\r\n\r\n",
+            b"GET / HTTP/1.1\r\nX-Request-Id: \x00\r\n\r\n",
+            &long,
+        ] {
+            let result = RequestMessage::from(data);
+
+            assert!(result.request_id().is_none());
+        }
+    }
+
+    #[test]
+    fn test_request_message_forwarded_for() {
+        let data = b"GET / HTTP/1.1\r\nX-Forwarded-For: 203.0.113.7, 10.0.0.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.forwarded_for() == Some(IpAddr::from([203, 0, 113, 7])));
+    }
+
+    #[test]
+    fn test_request_message_forwarded_last() {
+        let data = b"GET / HTTP/1.1\r\nX-Forwarded-For: 203.0.113.7\r\n\
+                     X-Forwarded-For: 192.0.2.1, 10.0.0.1:4711\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.forwarded_for() == Some(IpAddr::from([203, 0, 113, 7])));
+        assert!(result.forwarded_last() == Some(IpAddr::from([10, 0, 0, 1])));
+    }
+
+    #[test]
+    fn test_request_message_forwarded_for_with_port() {
+        let data = b"GET / HTTP/1.1\r\nX-Forwarded-For: [2001:db8::1]:4711\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.forwarded_for() == "2001:db8::1".parse().ok());
+    }
+
+    #[test]
+    fn test_request_message_forwarded_for_invalid() {
+        for value in [
+            b"".as_slice(),
+            b"unknown",
+            b"203.0.113.7 evil",
+            b"_hidden, 10.0.0.1",
+        ] {
+            let data = [
+                b"GET / HTTP/1.1\r\nX-Forwarded-For: ".as_slice(),
+                value,
+                b"\r\n\r\n",
+            ]
+            .concat();
+
+            let result = RequestMessage::from(data.as_slice());
+
+            assert!(result.forwarded_for().is_none());
+        }
+    }
+
+    #[test]
+    fn test_request_message_is_probe() {
+        let config = Config::default();
+
+        for path in [b"/healthz".as_slice(), b"/livez", b"/readyz"] {
+            let data = [b"GET ".as_slice(), path, b" HTTP/1.1\r\n\r\n"].concat();
+
+            assert!(RequestMessage::from(data.as_slice()).is_probe(&config));
+        }
+
+        let data = b"GET /slow HTTP/1.1\r\n\r\n";
+
+        assert!(!RequestMessage::from(data.as_slice()).is_probe(&config));
+    }
+
+    #[test]
+    fn test_request_message_is_probe_encoded() {
+        let config = Config {
+            fault_rate: 1.0,
+            ..Config::default()
+        };
+        let data = b"GET /health%7a HTTP/1.1\r\n\r\n";
+
+        let request = RequestMessage::from(data.as_slice());
+
+        // NOTE: it is answered as a health check, so it is spared like one, too
+        assert!(request.is_probe(&config));
+        assert!(!request.is_faulty(&config));
+        assert!(request.response(&config).code == RESP_204.code);
+    }
+
+    #[test]
+    fn test_request_message_is_continue_expected() {
+        let data = b"POST / HTTP/1.1\r\nExpect: 100-Continue\r\nContent-Length: 1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.is_continue_expected());
+    }
+
+    #[test]
+    fn test_request_message_is_continue_expected_with_http_1_0() {
+        let data = b"POST / HTTP/1.0\r\nExpect: 100-continue\r\nContent-Length: 1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_continue_expected());
+    }
+
+    #[test]
+    fn test_request_message_not_modified() {
+        let response = ResponseMessage {
+            body: Cow::Borrowed(b"Hello"),
+            etag: Some(Cow::Borrowed(b"\"5-f7d18982\"")),
+            ..RESP_200
+        };
+
+        for (data, expected) in [
+            (
+                b"GET / HTTP/1.1\r\nIf-None-Match: \"5-f7d18982\"\r\n\r\n".as_slice(),
+                true,
+            ),
+            (
+                b"HEAD / HTTP/1.1\r\nIf-None-Match: W/\"5-f7d18982\"\r\n\r\n",
+                true,
+            ),
+            (
+                b"GET / HTTP/1.1\r\nIf-None-Match: \"a\" , \"5-f7d18982\"\r\n\r\n",
+                true,
+            ),
+            (b"GET / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n", true),
+            (
+                b"GET / HTTP/1.1\r\nIf-None-Match: \"5-f7d18983\"\r\n\r\n",
+                false,
+            ),
+            (
+                b"GET / HTTP/1.1\r\nIf-None-Match: 5-f7d18982\r\n\r\n",
+                false,
+            ),
+            (b"POST / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n", false),
+            (b"GET / HTTP/1.1\r\n\r\n", false),
+        ] {
+            let result = RequestMessage::from(data).not_modified(&response);
+
+            assert!(result.is_some() == expected);
+            assert!(result.is_none_or(|result| result.code == 304 && result.body.is_empty()));
+        }
+    }
+
+    #[test]
+    fn test_request_message_not_modified_without_etag() {
+        let data = b"GET / HTTP/1.1\r\nIf-None-Match: *\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).not_modified(&RESP_200);
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_request_message_is_gzip_accepted() {
+        let data = b"GET / HTTP/1.1\r\nAccept-Encoding: br;q=1.0, GZIP;q=0.5\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.is_gzip_accepted());
+    }
+
+    #[test]
+    fn test_request_message_is_gzip_accepted_with_zero_quality() {
+        let data = b"GET / HTTP/1.1\r\nAccept-Encoding: gzip; q=0.00, deflate\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_gzip_accepted());
+    }
+
+    #[test]
+    fn test_request_message_is_gzip_accepted_without_header() {
+        let data = b"GET / HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(!result.is_gzip_accepted());
+    }
+
+    #[test]
+    fn test_request_message_response_204() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/healthz",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_204.code);
+        assert!(result.desc == RESP_204.desc);
+        assert!(result.http == RESP_204.http);
+        assert!(result.headers == RESP_204.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_204_custom_health() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/health",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+        static CONFIG: LazyLock<Config> = LazyLock::new(|| Config {
+            health: b"/health".to_vec(),
+            ..Config::default()
+        });
+
+        let result = data.response(&CONFIG);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_204.code);
+        assert!(result.desc == RESP_204.desc);
+    }
+
+    #[test]
+    fn test_request_message_response_404_default_health() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/healthz",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+        static CONFIG: LazyLock<Config> = LazyLock::new(|| Config {
+            health: b"/health".to_vec(),
+            ..Config::default()
+        });
+
+        let result = data.response(&CONFIG);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_404.code);
+        assert!(result.desc == RESP_404.desc);
+    }
+
+    #[test]
+    fn test_request_message_response_default() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/whatever",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+        static CONFIG: LazyLock<Config> = LazyLock::new(|| Config {
+            default: ResponseMessage::from_code(410),
+            ..Config::default()
+        });
+
+        let result = data.response(&CONFIG);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == 410);
+        assert!(result.desc == b"Gone");
+        assert!(result.http == RESP_404.http);
+        assert!(result.headers == RESP_404.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_metrics() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/metrics",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
         assert!(result.code == RESP_200.code);
         assert!(result.desc == RESP_200.desc);
-        assert!(result.http == RESP_200.http);
-        assert!(result.headers == RESP_200.headers);
+        assert!(result.headers == [b"Content-Type: text/plain; version=0.0.4; charset=utf-8"]);
+        assert!(result.body.starts_with(b"# HELP http_requests_total "));
+    }
+
+    #[test]
+    fn test_request_message_response_stats() {
+        let data = b"GET /stats HTTP/1.1\r\n\r\n";
+        let config = Config {
+            stats: Some(b"/stats".to_vec()),
+            ..Config::default()
+        };
+        metrics::record(404, Duration::ZERO);
+
+        let request = RequestMessage::from(data.as_slice());
+        let result = request.response(&config);
+
+        assert!(request.is_stats(&config));
+        assert!(result.code == RESP_200.code);
+        assert!(result.headers == [b"Content-Type: text/plain; charset=utf-8"]);
+        assert!(result.body.starts_with(b"uptime_seconds: "));
+        assert!(!result
+            .body
+            .windows(17)
+            .any(|line| line == b"\nrequests_4xx: 0\n"));
+    }
+
+    #[test]
+    fn test_request_message_response_stats_disabled() {
+        let data = b"GET /stats HTTP/1.1\r\n\r\n";
+
+        let request = RequestMessage::from(data.as_slice());
+        let result = request.response(&DEFAULT);
+
+        assert!(!request.is_stats(&DEFAULT));
+        assert!(result.code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_request_message_response_metrics_disabled() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/metrics",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+        let config = Config {
+            metrics: None,
+            ..Config::default()
+        };
+
+        let result = data.response(&config);
+
+        assert!(result.code == RESP_404.code);
+        assert!(result.body.is_empty());
+    }
+
+    #[test]
+    fn test_request_message_response_liveness() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/livez",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_204.code);
+        assert!(result.desc == RESP_204.desc);
+    }
+
+    #[test]
+    fn test_request_message_response_readiness() {
+        let _lock = READINESS.lock().unwrap();
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/readyz",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_204.code);
+        assert!(result.desc == RESP_204.desc);
+    }
+
+    #[test]
+    fn test_request_message_response_health_json() {
+        let config = Config {
+            health_json: true,
+            ..Config::default()
+        };
+
+        for path in [b"/healthz".as_slice(), b"/livez"] {
+            let data = [b"GET ", path, b" HTTP/1.1\r\n\r\n"].concat();
+
+            let result = RequestMessage::from(data.as_slice()).response(&config);
+
+            assert!(result.code == RESP_200.code);
+            assert!(result.headers == [b"Content-Type: application/json"]);
+            assert!(result.body.as_ref() == br#"{"status":"ok"}"#);
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_readiness_json_draining() {
+        let _lock = READINESS.lock().unwrap();
+        let config = Config {
+            health_json: true,
+            ..Config::default()
+        };
+        let data = &RequestMessage::from(b"GET /readyz HTTP/1.1\r\n\r\n".as_slice());
+
+        READY.store(false, Ordering::Relaxed);
+        let result = data.response(&config);
+        READY.store(true, Ordering::Relaxed);
+
+        assert!(result.code == RESP_503.code);
+        assert!(result.headers == [b"Content-Type: application/json"]);
+        assert!(result.body.as_ref() == br#"{"status":"draining"}"#);
+        assert!(data.response(&config).body.as_ref() == br#"{"status":"ok"}"#);
+    }
+
+    #[test]
+    fn test_request_message_response_admin_drain() {
+        let _lock = READINESS.lock().unwrap();
+        let config = admin_config();
+        let drain = b"POST /admin/drain HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n";
+        let undrain = b"POST /admin/undrain HTTP/1.1\r\nAuthorization: bearer secret\r\n\r\n";
+        let readiness = &RequestMessage::from(b"GET /readyz HTTP/1.1\r\n\r\n".as_slice());
+
+        let result = RequestMessage::from(drain.as_slice()).response(&config);
+
+        assert!(result.code == RESP_204.code);
+        assert!(readiness.response(&config).code == RESP_503.code);
+
+        let result = RequestMessage::from(undrain.as_slice()).response(&config);
+
+        assert!(result.code == RESP_204.code);
+        assert!(readiness.response(&config).code == RESP_204.code);
+    }
+
+    #[test]
+    fn test_request_message_response_admin_401() {
+        let config = admin_config();
+
+        for data in [
+            b"POST /admin/drain HTTP/1.1\r\n\r\n".as_slice(),
+            b"POST /admin/drain HTTP/1.1\r\nAuthorization: Bearer secreT\r\n\r\n",
+            b"POST /admin/drain HTTP/1.1\r\nAuthorization: Bearer secret2\r\n\r\n",
+            b"POST /admin/drain HTTP/1.1\r\nAuthorization: Basic secret\r\n\r\n",
+        ] {
+            let result = RequestMessage::from(data).response(&config);
+
+            assert!(result.code == RESP_401.code);
+            assert!(result.headers == [b"WWW-Authenticate: Bearer"]);
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_admin_405() {
+        let data = b"GET /admin/drain HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n";
+
+        let config = admin_config();
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_405.code);
+        assert!(result.headers == [b"Allow: POST"]);
+    }
+
+    #[test]
+    fn test_request_message_response_admin_disabled() {
+        let data = b"POST /admin/drain HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&DEFAULT);
+
+        assert!(result.code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_request_message_response_root() {
+        let config = Config {
+            root: Some(ResponseMessage {
+                body: Cow::Borrowed(b"Hello"),
+                ..RESP_200
+            }),
+            default: ResponseMessage::from_code(410),
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(b"GET / HTTP/1.1\r\n\r\n".as_slice()).response(&config);
+
+        assert!(result.code == RESP_200.code);
+        assert!(result.body == b"Hello".as_slice());
+
+        let result = RequestMessage::from(b"GET /x HTTP/1.1\r\n\r\n".as_slice()).response(&config);
+
+        assert!(result.code == 410);
+    }
+
+    #[test]
+    fn test_request_message_response_root_unset() {
+        let result = RequestMessage::from(b"GET / HTTP/1.1\r\n\r\n".as_slice()).response(&DEFAULT);
+
+        assert!(result.code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_request_message_response_404() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/whatever",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_404.code);
+        assert!(result.desc == RESP_404.desc);
+        assert!(result.http == RESP_404.http);
+        assert!(result.headers == RESP_404.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_400_empty() {
+        let data = &RequestMessage {
+            method: b"",
+            path: b"",
+            http: b"",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_400.code);
+        assert!(result.desc == RESP_400.desc);
+        assert!(result.http == RESP_400.http);
+        assert!(result.headers == RESP_400.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_400_invalid_path() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"\\whatever",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_400.code);
+        assert!(result.desc == RESP_400.desc);
+        assert!(result.http == RESP_400.http);
+        assert!(result.headers == RESP_400.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_400_non_ascii() {
+        const SKULL: &[u8] = "💀".as_bytes();
+        let data = &RequestMessage {
+            method: b"GET",
+            path: SKULL,
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_400.code);
+        assert!(result.desc == RESP_400.desc);
+        assert!(result.http == RESP_400.http);
+        assert!(result.headers == RESP_400.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_405() {
+        let data = &RequestMessage {
+            method: b"CONNECT",
+            path: b"/",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_405.code);
+        assert!(result.desc == RESP_405.desc);
+        assert!(result.http == RESP_405.http);
+        assert!(result.headers == [b"Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH"]);
+    }
+
+    #[test]
+    fn test_request_message_response_trace() {
+        let data = b"TRACE /foo HTTP/1.1\r\nHost: example.com\r\nCookie: a=b\r\nX-Test: 1\r\n\r\n";
+        let config = Config {
+            allow_trace: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_200.code);
+        assert!(result.headers == HEADERS_TRACE);
+        assert!(
+            result.body.as_ref() == b"TRACE /foo HTTP/1.1\r\nHost: example.com\r\nX-Test: 1\r\n"
+        );
+    }
+
+    #[test]
+    fn test_request_message_response_trace_disabled() {
+        let data = b"TRACE /foo HTTP/1.1\r\nHost: example.com\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&DEFAULT);
+
+        assert!(result.code == RESP_405.code);
+        assert!(result.headers == [b"Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH"]);
+        assert!(result.body.is_empty());
+    }
+
+    #[test]
+    fn test_request_message_response_405_with_trace() {
+        let data = b"CONNECT example.com:443 HTTP/1.1\r\n\r\n";
+        let config = Config {
+            allow_trace: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.headers == [b"Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH, TRACE"]);
+    }
+
+    #[test]
+    fn test_request_message_response_400_method_with_tab() {
+        let data = &RequestMessage::from(b"GE\tT / HTTP/1.1\r\n\r\n".as_slice());
+
+        let result = data.response(&DEFAULT);
+
+        assert!(data.method == b"GE\tT");
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_400_method_with_separator() {
+        let data = &RequestMessage {
+            method: b"GET,",
+            path: b"/",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_501() {
+        let data = &RequestMessage {
+            method: b"TEST",
+            path: b"/",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_501.code);
+        assert!(result.desc == RESP_501.desc);
+        assert!(result.headers == RESP_501.headers);
+    }
+
+    #[test]
+    fn test_request_message_response_connect() {
+        let data = &RequestMessage {
+            method: b"CONNECT",
+            path: b"example.com:443",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_405.code);
+        assert!(result.headers == [b"Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH"]);
+    }
+
+    #[test]
+    fn test_request_message_response_connect_without_port() {
+        let data = &RequestMessage {
+            method: b"CONNECT",
+            path: b"example.com",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_400_authority() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"example.com:443",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_400_without_version() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/",
+            http: b"",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_from_over_long_path() {
+        let data = [
+            b"GET /".as_slice(),
+            &[b'a'; PATH_LIMIT],
+            b" HTTP/1.1\r\n\r\n",
+        ]
+        .concat();
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.path.len() == PATH_LIMIT + 1);
+        assert!(result.http == b"HTTP/1.1");
+        assert!(result.response(&DEFAULT).code == RESP_414.code);
+    }
+
+    #[test]
+    fn test_request_message_response_origin_form() {
+        let data = b"GET /healthz HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&DEFAULT);
+
+        assert!(result.code == RESP_204.code);
+    }
+
+    #[test]
+    fn test_request_message_response_absolute_form() {
+        for data in [
+            b"GET http://example.com/healthz HTTP/1.1\r\n\r\n".as_slice(),
+            b"HEAD HTTPS://example.com:8080/healthz HTTP/1.1\r\n\r\n",
+            b"GET http://[::1]/h%65althz HTTP/1.1\r\n\r\n",
+        ] {
+            let result = RequestMessage::from(data).response(&DEFAULT);
+
+            assert!(result.code == RESP_204.code);
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_absolute_form_without_path() {
+        let data = b"GET http://example.com?a=1 HTTP/1.1\r\n\r\n";
+        let config = Config {
+            root: Some(ResponseMessage::from_code(200)),
+            ..Config::default()
+        };
+
+        let request = RequestMessage::from(data.as_slice());
+
+        assert!(request.origin().as_ref() == b"/?a=1");
+        assert!(request.response(&config).code == RESP_404.code);
+
+        let data = b"GET http://example.com HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == 200);
+    }
+
+    #[test]
+    fn test_request_message_response_absolute_form_invalid() {
+        for data in [
+            b"GET ftp://example.com/healthz HTTP/1.1\r\n\r\n".as_slice(),
+            b"GET http:/example.com/healthz HTTP/1.1\r\n\r\n",
+            b"GET http:///healthz HTTP/1.1\r\n\r\n",
+            b"GET http://user@example.com/healthz HTTP/1.1\r\n\r\n",
+        ] {
+            let result = RequestMessage::from(data).response(&DEFAULT);
+
+            assert!(result.code == RESP_400.code);
+        }
     }
 
     #[test]
-    fn test_request_message_response_404() {
+    fn test_request_message_response_authority_form_for_get() {
+        let data = b"GET example.com:443 HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_options_asterisk() {
         let data = &RequestMessage {
-            method: b"GET",
-            path: b"/whatever",
+            method: b"OPTIONS",
+            path: b"*",
             http: b"HTTP/1.1",
+            headers: Vec::new(),
         };
 
-        let result = data.response();
+        let result = data.response(&DEFAULT);
 
         assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.code == RESP_204.code);
+        assert!(result.desc == RESP_204.desc);
+        assert!(result.headers == [b"Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH"]);
+    }
+
+    #[test]
+    fn test_request_message_response_options_path() {
+        let data = &RequestMessage {
+            method: b"OPTIONS",
+            path: b"/foo",
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
         assert!(result.code == RESP_404.code);
-        assert!(result.desc == RESP_404.desc);
-        assert!(result.http == RESP_404.http);
         assert!(result.headers == RESP_404.headers);
     }
 
     #[test]
-    fn test_request_message_response_400_empty() {
+    fn test_request_message_response_options_preflight() {
         let data = &RequestMessage {
-            method: b"",
-            path: b"",
-            http: b"",
+            method: b"OPTIONS",
+            path: b"/metrics",
+            http: b"HTTP/1.1",
+            headers: vec![(b"Access-Control-Request-Method", b"GET")],
+        };
+        let config = Config {
+            cors_origin: Some(b"Access-Control-Allow-Origin: *".to_vec()),
+            ..Config::default()
         };
 
-        let result = data.response();
+        let result = data.response(&config);
 
-        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
-        assert!(result.code == RESP_400.code);
-        assert!(result.desc == RESP_400.desc);
-        assert!(result.http == RESP_400.http);
-        assert!(result.headers == RESP_400.headers);
+        assert!(result.code == RESP_204.code);
+        assert!(result.headers == HEADERS_PREFLIGHT);
     }
 
     #[test]
-    fn test_request_message_response_400_invalid_path() {
+    fn test_request_message_response_options_preflight_without_cors() {
+        let data = &RequestMessage {
+            method: b"OPTIONS",
+            path: b"/metrics",
+            http: b"HTTP/1.1",
+            headers: vec![(b"Access-Control-Request-Method", b"GET")],
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_request_message_redirect() {
+        let data =
+            b"GET /foo?bar=1 HTTP/1.1\r\nHost: example.com:8080\r\nX-Forwarded-Proto: http\r\n\r\n";
+        let config = Config {
+            force_https: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.redirect(&config).unwrap() == b"Location: https://example.com/foo?bar=1");
+        assert!(result.response(&config).code == RESP_308.code);
+    }
+
+    #[test]
+    fn test_request_message_redirect_with_ipv6_host() {
+        let data = b"GET / HTTP/1.1\r\nHost: [::1]\r\nX-Forwarded-Proto: HTTP, https\r\n\r\n";
+        let config = Config {
+            force_https: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.redirect(&config).unwrap() == b"Location: https://[::1]/");
+    }
+
+    #[test]
+    fn test_request_message_redirect_over_https() {
+        let data = b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-Proto: https\r\n\r\n";
+        let config = Config {
+            force_https: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.redirect(&config).is_none());
+        assert!(result.response(&config).code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_request_message_redirect_with_invalid_host() {
+        let config = Config {
+            force_https: true,
+            ..Config::default()
+        };
+
+        for host in [b"".as_slice(), b"evil.com/path", b"user@evil.com", b"a b"] {
+            let data = [
+                b"GET / HTTP/1.1\r\nX-Forwarded-Proto: http\r\nHost: ".as_slice(),
+                host,
+                b"\r\n\r\n",
+            ]
+            .concat();
+
+            let result = RequestMessage::from(data.as_slice());
+
+            assert!(result.redirect(&config).is_none());
+        }
+    }
+
+    #[test]
+    fn test_request_message_redirect_disabled() {
+        let data = b"GET / HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-Proto: http\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.redirect(&DEFAULT).is_none());
+    }
+
+    #[test]
+    fn test_request_message_redirect_health() {
+        let data = b"GET /healthz HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-Proto: http\r\n\r\n";
+        let config = Config {
+            force_https: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice());
+
+        assert!(result.response(&config).code == RESP_204.code);
+    }
+
+    #[test]
+    fn test_route() {
+        let config = Config {
+            routes: vec![
+                (b"/api/v2".to_vec(), ResponseMessage::from_code(200)),
+                (b"/api".to_vec(), ResponseMessage::from_code(503)),
+                (b"/".to_vec(), ResponseMessage::from_code(410)),
+            ],
+            ..Config::default()
+        };
+        let code = |path: &[u8]| route(path, &config.routes).map(|response| response.code);
+
+        assert!(code(b"/api/v2/users") == Some(200));
+        assert!(code(b"/api/v2") == Some(200));
+        assert!(code(b"/api/v2?q=1") == Some(200));
+        assert!(code(b"/api/v20") == Some(503));
+        assert!(code(b"/api/v1") == Some(503));
+        assert!(code(b"/api") == Some(503));
+        assert!(code(b"/apis") == Some(410));
+        assert!(code(b"/") == Some(410));
+    }
+
+    #[test]
+    fn test_route_without_match() {
+        let config = Config {
+            routes: vec![(b"/api".to_vec(), ResponseMessage::from_code(503))],
+            ..Config::default()
+        };
+
+        assert!(route(b"/apis", &config.routes).is_none());
+        assert!(route(b"/", &config.routes).is_none());
+    }
+
+    #[test]
+    fn test_request_message_response_route() {
+        let data = b"GET /api/users HTTP/1.1\r\n\r\n";
+        let config = Config {
+            routes: vec![(b"/api".to_vec(), ResponseMessage::from_code(503))],
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_503.code);
+        assert!(result.desc == RESP_503.desc);
+    }
+
+    fn vhost_config() -> Config {
+        Config {
+            routes: vec![(b"/api".to_vec(), ResponseMessage::from_code(503))],
+            vhosts: vec![
+                (
+                    b"a.example".to_vec(),
+                    Vhost {
+                        default: Some(ResponseMessage::from_code(410)),
+                        routes: vec![(b"/api/v2".to_vec(), ResponseMessage::from_code(200))],
+                    },
+                ),
+                (
+                    b"b.example:8080".to_vec(),
+                    Vhost {
+                        default: None,
+                        routes: vec![(b"/foo".to_vec(), ResponseMessage::from_code(204))],
+                    },
+                ),
+            ],
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_vhost() {
+        let config = vhost_config();
+        let code = |path: &str, host: &str| {
+            let data = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n\r\n");
+            RequestMessage::from(data.as_bytes()).response(&config).code
+        };
+
+        assert!(code("/foo", "a.example") == 410);
+        assert!(code("/foo", "A.Example:8080") == 410);
+        assert!(code("/foo", "b.example:8080") == 204);
+        assert!(code("/foo", "b.example") == 404);
+        assert!(code("/foo", "c.example") == 404);
+        assert!(code("/api/v2", "a.example") == 200);
+        assert!(code("/api/v1", "a.example") == 503);
+        assert!(code("/api/v2", "c.example") == 503);
+    }
+
+    #[test]
+    fn test_request_message_response_vhost_without_host() {
+        let data = b"GET /foo HTTP/1.1\r\n\r\n";
+
+        let config = vhost_config();
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_request_message_response_strict_host() {
+        let config = Config {
+            strict_host: true,
+            ..vhost_config()
+        };
+        let code = |path: &str, host: &str| {
+            let data = format!("GET {path} HTTP/1.1\r\nHost: {host}\r\n\r\n");
+            RequestMessage::from(data.as_bytes()).response(&config).code
+        };
+
+        assert!(code("/foo", "c.example") == RESP_421.code);
+        assert!(code("/api/v2", "c.example") == RESP_421.code);
+        assert!(code("/foo", "a.example") == 410);
+        assert!(code("/healthz", "c.example") == RESP_204.code);
+        assert!(
+            RequestMessage::from(b"GET /foo HTTP/1.0\r\n\r\n".as_slice())
+                .response(&config)
+                .code
+                == 421
+        );
+    }
+
+    #[test]
+    fn test_request_message_response_echo() {
+        let data =
+            b"POST /debug/echo HTTP/1.1\r\nHost: example.com\r\nX-Test: a\x1b[31mb\\\r\n\r\n";
+        let config = Config {
+            echo: true,
+            ..Config::default()
+        };
+
+        let result = RequestMessage::from(data.as_slice()).response(&config);
+
+        assert!(result.code == RESP_200.code);
+        assert!(result.headers == [b"Content-Type: text/plain; charset=utf-8"]);
+        assert!(
+            result.body.as_ref()
+                == b"method: POST\npath: /debug/echo\nversion: HTTP/1.1\n\
+                     header: Host: example.com\nheader: X-Test: a\\x1b[31mb\\\\\n"
+        );
+    }
+
+    #[test]
+    fn test_request_message_response_echo_disabled() {
+        let data = b"GET /debug/echo HTTP/1.1\r\n\r\n";
+
+        let result = RequestMessage::from(data.as_slice()).response(&DEFAULT);
+
+        assert!(result.code == RESP_404.code);
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        for (path, expected) in [
+            (b"/healthz".as_slice(), b"/healthz".as_slice()),
+            (b"//healthz", b"/healthz"),
+            (b"/healthz/", b"/healthz"),
+            (b"/./healthz", b"/healthz"),
+            (b"/a//b/../c/.?q=/x//", b"/a/c?q=/x//"),
+            (b"/../..//", b"/"),
+            (b"/", b"/"),
+            (b"*", b"*"),
+        ] {
+            assert!(normalize_path(path).as_ref() == expected);
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_normalized() {
+        let config = Config {
+            normalize_path: true,
+            ..Config::default()
+        };
+
+        for path in ["/healthz", "//healthz", "/healthz/", "/./healthz"] {
+            let data = format!("GET {path} HTTP/1.1\r\n\r\n");
+            let request = RequestMessage::from(data.as_bytes());
+
+            assert!(request.response(&config).code == RESP_204.code);
+            assert!(request.is_probe(&config));
+            assert!(request.path == path.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_not_normalized() {
+        for path in ["//healthz", "/healthz/", "/./healthz"] {
+            let data = format!("GET {path} HTTP/1.1\r\n\r\n");
+            let request = RequestMessage::from(data.as_bytes());
+
+            assert!(request.response(&DEFAULT).code == RESP_404.code);
+            assert!(!request.is_probe(&DEFAULT));
+        }
+    }
+
+    #[test]
+    fn test_sanitize_path() {
+        assert!(sanitize_path(b"/foo/bar?baz=1") == Some(b"/foo/bar?baz=1".as_slice()));
+        assert!(sanitize_path(b"/foo\r\nSet-Cookie: a=b").is_none());
+        assert!(sanitize_path(b"/foo\x00").is_none());
+        assert!(sanitize_path(b"/foo\x7f").is_none());
+    }
+
+    #[test]
+    fn test_decode_path() {
+        assert!(matches!(
+            decode_path(b"/healthz"),
+            Some(Cow::Borrowed(b"/healthz"))
+        ));
+        assert!(decode_path(b"/health%7a").as_deref() == Some(b"/healthz".as_slice()));
+        assert!(decode_path(b"/a%2Fb%20c").as_deref() == Some(b"/a/b c".as_slice()));
+        assert!(decode_path(b"/a%zz").is_none());
+        assert!(decode_path(b"/a%4").is_none());
+        assert!(decode_path(b"/a%").is_none());
+    }
+
+    #[test]
+    fn test_request_message_response_encoded_health() {
         let data = &RequestMessage {
             method: b"GET",
-            path: b"\\whatever",
+            path: b"/health%7A",
             http: b"HTTP/1.1",
+            headers: Vec::new(),
         };
 
-        let result = data.response();
+        let result = data.response(&DEFAULT);
 
-        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
-        assert!(result.code == RESP_400.code);
-        assert!(result.desc == RESP_400.desc);
-        assert!(result.http == RESP_400.http);
-        assert!(result.headers == RESP_400.headers);
+        assert!(result.code == RESP_204.code);
     }
 
     #[test]
-    fn test_request_message_response_400_non_ascii() {
-        const SKULL: &[u8] = "💀".as_bytes();
+    fn test_request_message_response_400_invalid_encoding() {
+        for path in [b"/a%zz".as_slice(), b"/a%4"] {
+            let data = &RequestMessage {
+                method: b"GET",
+                path,
+                http: b"HTTP/1.1",
+                headers: Vec::new(),
+            };
+
+            let result = data.response(&DEFAULT);
+
+            assert!(result.code == RESP_400.code);
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_400_encoded_control_characters() {
         let data = &RequestMessage {
             method: b"GET",
-            path: SKULL,
+            path: b"/a%0d%0aSet-Cookie:%20a=b",
             http: b"HTTP/1.1",
+            headers: Vec::new(),
         };
 
-        let result = data.response();
+        let result = data.response(&DEFAULT);
 
-        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
         assert!(result.code == RESP_400.code);
-        assert!(result.desc == RESP_400.desc);
-        assert!(result.http == RESP_400.http);
-        assert!(result.headers == RESP_400.headers);
     }
 
     #[test]
-    fn test_request_message_response_405() {
+    fn test_request_message_response_400_control_characters() {
+        for path in [
+            b"/a\r\nb".as_slice(),
+            b"/a\nb",
+            b"/a\tb",
+            b"/a\x1bb",
+            b"/a\x7f",
+        ] {
+            let data = &RequestMessage {
+                method: b"GET",
+                path,
+                http: b"HTTP/1.1",
+                headers: Vec::new(),
+            };
+
+            let result = data.response(&DEFAULT);
+
+            assert!(result.code == RESP_400.code);
+        }
+    }
+
+    #[test]
+    fn test_request_message_response_400_raw_control_characters() {
+        let data = &RequestMessage::from(b"GET /a\x0bb HTTP/1.1\r\n\r\n".as_slice());
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_400_asterisk() {
         let data = &RequestMessage {
-            method: b"TEST",
-            path: b"/",
+            method: b"GET",
+            path: b"*",
             http: b"HTTP/1.1",
+            headers: Vec::new(),
         };
 
-        let result = data.response();
+        let result = data.response(&DEFAULT);
 
-        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
-        assert!(result.code == RESP_405.code);
-        assert!(result.desc == RESP_405.desc);
-        assert!(result.http == RESP_405.http);
-        assert!(result.headers == RESP_405.headers);
+        assert!(result.code == RESP_400.code);
     }
 
     #[test]
     fn test_request_message_response_414() {
+        let path = [b"/".as_slice(), &[b'a'; PATH_LIMIT]].concat();
         let data = &RequestMessage {
             method: b"GET",
-            path: b"/too-long-path",
-            http: b"",
+            path: &path,
+            http: b"HTTP/1.1",
+            headers: Vec::new(),
         };
 
-        let result = data.response();
+        let result = data.response(&DEFAULT);
 
         assert!(result.type_id() == TypeId::of::<ResponseMessage>());
         assert!(result.code == RESP_414.code);
@@ -292,15 +2600,26 @@ mod tests {
         assert!(result.headers == RESP_414.headers);
     }
 
+    #[test]
+    fn test_request_message_response_505_to_h2_preface() {
+        let data = RequestMessage::from(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n".as_slice());
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_505.code);
+        assert!(result.desc == RESP_505.desc);
+    }
+
     #[test]
     fn test_request_message_response_505() {
         let data = &RequestMessage {
             method: b"GET",
             path: b"/",
             http: b"HTTP/1.2",
+            headers: Vec::new(),
         };
 
-        let result = data.response();
+        let result = data.response(&DEFAULT);
 
         assert!(result.type_id() == TypeId::of::<ResponseMessage>());
         assert!(result.code == RESP_505.code);
@@ -309,6 +2628,30 @@ mod tests {
         assert!(result.headers == RESP_505.headers);
     }
 
+    #[test]
+    fn test_request_message_response_400_malformed_http() {
+        let data = &RequestMessage {
+            method: b"GET",
+            path: b"/",
+            http: b"HTTP/x.y",
+            headers: Vec::new(),
+        };
+
+        let result = data.response(&DEFAULT);
+
+        assert!(result.code == RESP_400.code);
+    }
+
+    #[test]
+    fn test_request_message_response_400_http_with_trailing_garbage() {
+        let data = &RequestMessage::from(b"GET / HTTP/1.1-extra\r\n\r\n".as_slice());
+
+        let result = data.response(&DEFAULT);
+
+        assert!(data.http == b"HTTP/1.1-extra");
+        assert!(result.code == RESP_400.code);
+    }
+
     #[test]
     fn test_response_message_with_status() {
         let result = ResponseMessage::with_status(204, b"No Content");
@@ -317,6 +2660,85 @@ mod tests {
         assert!(result.http == b"HTTP/1.1");
         assert!(result.code == 204);
         assert!(result.desc == b"No Content");
-        assert!(result.headers[0] == b"Connection: close");
+        assert!(result.headers.is_empty());
+    }
+
+    #[test]
+    fn test_response_message_with_status_without_body() {
+        let result = ResponseMessage::with_status(404, b"Not Found");
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.headers.is_empty());
+        assert!(result.body.is_empty());
+        assert!(!result.is_bodiless());
+    }
+
+    #[test]
+    fn test_response_message_to_bytes_with_body() {
+        let result = ResponseMessage {
+            headers: &[b"Content-Type: text/html"],
+            body: Cow::Borrowed(b"<h1>404 Not Found</h1>"),
+            ..RESP_404
+        };
+
+        let result = result.to_bytes(&[]);
+
+        assert!(
+            result
+                == b"HTTP/1.1 404 Not Found\r\nContent-Type: text/html\r\nContent-Length: 22\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_reason_phrase() {
+        assert!(reason_phrase(404) == b"Not Found");
+        assert!(reason_phrase(503) == b"Service Unavailable");
+        assert!(reason_phrase(599) == b"Unknown");
+    }
+
+    #[test]
+    fn test_reason_phrase_matches_constants() {
+        for response in [
+            RESP_204, RESP_400, RESP_404, RESP_405, RESP_414, RESP_431, RESP_505,
+        ] {
+            assert!(response.desc == reason_phrase(response.code));
+        }
+    }
+
+    #[test]
+    fn test_response_message_from_code() {
+        let result = ResponseMessage::from_code(410);
+
+        assert!(result.type_id() == TypeId::of::<ResponseMessage>());
+        assert!(result.http == b"HTTP/1.1");
+        assert!(result.code == 410);
+        assert!(result.desc == b"Gone");
+        assert!(result.headers == RESP_404.headers);
+    }
+
+    #[test]
+    fn test_response_message_to_bytes() {
+        let result = RESP_404.to_bytes(&[]);
+
+        assert!(result == b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n");
+    }
+
+    #[test]
+    fn test_response_message_to_bytes_with_extra() {
+        let result =
+            RESP_404.to_bytes(&[b"Connection: close", b"Date: Sun, 06 Nov 1994 08:49:37 GMT"]);
+
+        assert!(
+            result
+                == b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\
+                Date: Sun, 06 Nov 1994 08:49:37 GMT\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_response_message_to_bytes_204_without_body() {
+        let result = RESP_204.to_bytes(&[]);
+
+        assert!(result == b"HTTP/1.1 204 No Content\r\n\r\n");
     }
 }
@@ -0,0 +1,32 @@
+//! The HTTP logic of the 404 container: parsing requests, and responding to them.
+//!
+//! The binary is a thin wrapper around [`run`], with [`process`] serving each connection, while
+//! [`RequestMessage`], [`ResponseMessage`], and [`Config`] let the same responses be produced
+//! without any networking, e.g.:
+//!
+//! ```
+//! use http_404::{Config, RequestMessage};
+//!
+//! let config = Config::default();
+//! let request = RequestMessage::from(b"GET /foo HTTP/1.1\r\n\r\n".as_slice());
+//! let response = request.response(&config);
+//!
+//! assert!(response.code == 404);
+//! ```
+
+mod config;
+mod http;
+mod limit;
+mod log;
+mod metrics;
+mod server;
+mod state;
+mod tls;
+mod utils;
+
+pub use crate::config::Config;
+pub use crate::http::{reason_phrase, RequestMessage, ResponseMessage};
+pub use crate::server::{check, process, run};
+
+const CRLF: &[u8; 2] = b"\r\n";
+const SEP: &[u8; 1] = b" ";
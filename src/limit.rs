@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Instant;
+
+const NANOS: u64 = 1_000_000_000;
+const SWEEP_MIN: usize = 1024;
+
+/// The moment bucket times are counted from, i.e. roughly when the server has started.
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// The bucket every request takes from, whoever it comes from.
+pub static GLOBAL: Bucket = Bucket::new();
+
+/// The buckets of each client address, taken from along with the global one.
+static CLIENTS: LazyLock<Mutex<Clients>> = LazyLock::new(|| {
+    Mutex::new(Clients {
+        buckets: HashMap::new(),
+        sweep_at: SWEEP_MIN,
+    })
+});
+
+/// Represents buckets per client address, swept of idle ones as they pile up.
+struct Clients {
+    buckets: HashMap<IpAddr, Bucket>,
+    sweep_at: usize,
+}
+
+impl Clients {
+    /// Takes a token out of a given client bucket at a given Rate, as of a given moment.
+    fn take_at(&mut self, client: IpAddr, rate: Rate, now: u64) -> bool {
+        // NOTE: a full bucket is as good as a new one, so dropping it loses nothing
+        if self.buckets.len() >= self.sweep_at {
+            self.buckets.retain(|_, bucket| !bucket.is_full_at(now));
+            self.sweep_at = (self.buckets.len() * 2).max(SWEEP_MIN);
+        }
+
+        self.buckets
+            .entry(client)
+            .or_insert_with(Bucket::new)
+            .take_at(rate, now)
+    }
+
+    /// Puts a token taken at a given Rate back into a given client bucket, if it is still there.
+    fn refund(&mut self, client: IpAddr, rate: Rate) {
+        if let Some(bucket) = self.buckets.get(&client) {
+            bucket.refund(rate);
+        }
+    }
+}
+
+/// Takes a token out of the bucket of a given client address at a given Rate, and returns
+/// whether there was one.
+pub fn take_for(client: IpAddr, rate: Rate) -> bool {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|err| err.into_inner());
+
+    clients.take_at(client, rate, now())
+}
+
+/// Puts a token taken at a given Rate back into the bucket of a given client address, e.g. when
+/// the request it has been taken for is refused anyway.
+pub fn refund_for(client: IpAddr, rate: Rate) {
+    let mut clients = CLIENTS.lock().unwrap_or_else(|err| err.into_inner());
+
+    clients.refund(client, rate);
+}
+
+/// Represents a rate of requests per second, with a burst of them allowed at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rate {
+    pub per_second: u32,
+    pub burst: u32,
+}
+
+/// Represents a token bucket, refilled at a given Rate as time goes by.
+///
+/// It keeps the moment it is going to be full again (as in GCRA), instead of a count of tokens
+/// and a moment of the last refill, so a single atomic is enough.
+pub struct Bucket {
+    full_at: AtomicU64,
+}
+
+impl Bucket {
+    /// Creates a new Bucket, full from the start.
+    pub const fn new() -> Bucket {
+        Bucket {
+            full_at: AtomicU64::new(0),
+        }
+    }
+
+    /// Takes a token out of the Bucket at a given Rate, and returns whether there was one.
+    pub fn take(&self, rate: Rate) -> bool {
+        self.take_at(rate, now())
+    }
+
+    /// Takes a token out of the Bucket at a given Rate, as of a given moment in nanoseconds.
+    fn take_at(&self, rate: Rate, now: u64) -> bool {
+        let interval = interval(rate);
+        let capacity = interval * u64::from(rate.burst);
+
+        self.full_at
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |full_at| {
+                let next = full_at.max(now) + interval;
+                (next - now <= capacity).then_some(next)
+            })
+            .is_ok()
+    }
+
+    /// Puts a token taken at a given Rate back into the Bucket.
+    fn refund(&self, rate: Rate) {
+        let interval = interval(rate);
+
+        self.full_at
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |full_at| {
+                Some(full_at.saturating_sub(interval))
+            })
+            .ok();
+    }
+
+    /// Checks if the Bucket is full again as of a given moment, i.e. as good as a new one.
+    fn is_full_at(&self, now: u64) -> bool {
+        self.full_at.load(Ordering::Relaxed) <= now
+    }
+}
+
+/// Returns nanoseconds between tokens at a given Rate.
+fn interval(rate: Rate) -> u64 {
+    NANOS / u64::from(rate.per_second.max(1))
+}
+
+/// Returns nanoseconds since the start.
+fn now() -> u64 {
+    u64::try_from(START.elapsed().as_nanos()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: Rate = Rate {
+        per_second: 2,
+        burst: 3,
+    };
+
+    #[test]
+    fn test_bucket_take_burst() {
+        let bucket = Bucket::new();
+
+        let result: Vec<bool> = (0..4).map(|_| bucket.take_at(RATE, NANOS)).collect();
+
+        assert!(result == [true, true, true, false]);
+    }
+
+    #[test]
+    fn test_bucket_take_refilled() {
+        let bucket = Bucket::new();
+
+        for _ in 0..3 {
+            bucket.take_at(RATE, NANOS);
+        }
+
+        assert!(!bucket.take_at(RATE, NANOS + NANOS / 4));
+        assert!(bucket.take_at(RATE, NANOS + NANOS / 2));
+        assert!(!bucket.take_at(RATE, NANOS + NANOS / 2));
+    }
+
+    #[test]
+    fn test_bucket_take_at_maximum_rate() {
+        let rate = Rate {
+            per_second: 1_000_000_000,
+            burst: 1,
+        };
+        let bucket = Bucket::new();
+
+        assert!(bucket.take_at(rate, NANOS));
+        assert!(!bucket.take_at(rate, NANOS));
+    }
+
+    #[test]
+    fn test_bucket_refund() {
+        let bucket = Bucket::new();
+
+        for _ in 0..3 {
+            bucket.take_at(RATE, NANOS);
+        }
+        bucket.refund(RATE);
+
+        assert!(bucket.take_at(RATE, NANOS));
+        assert!(!bucket.take_at(RATE, NANOS));
+    }
+
+    #[test]
+    fn test_clients_take() {
+        let mut clients = Clients {
+            buckets: HashMap::new(),
+            sweep_at: SWEEP_MIN,
+        };
+        let (one, other) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+
+        let result: Vec<bool> = (0..4).map(|_| clients.take_at(one, RATE, NANOS)).collect();
+
+        assert!(result == [true, true, true, false]);
+        assert!(clients.take_at(other, RATE, NANOS));
+    }
+
+    #[test]
+    fn test_clients_refund() {
+        let mut clients = Clients {
+            buckets: HashMap::new(),
+            sweep_at: SWEEP_MIN,
+        };
+        let (one, other) = ("192.0.2.1".parse().unwrap(), "192.0.2.2".parse().unwrap());
+
+        for _ in 0..3 {
+            clients.take_at(one, RATE, NANOS);
+        }
+        clients.refund(one, RATE);
+        clients.refund(other, RATE);
+
+        assert!(clients.take_at(one, RATE, NANOS));
+        assert!(!clients.take_at(one, RATE, NANOS));
+        assert!(!clients.buckets.contains_key(&other));
+    }
+
+    #[test]
+    fn test_clients_sweep() {
+        let mut clients = Clients {
+            buckets: HashMap::new(),
+            sweep_at: SWEEP_MIN,
+        };
+
+        for index in 0..SWEEP_MIN as u32 {
+            clients.take_at(IpAddr::from(index.to_be_bytes()), RATE, NANOS);
+        }
+        clients.take_at("192.0.2.1".parse().unwrap(), RATE, 60 * NANOS);
+
+        assert!(clients.buckets.len() == 1);
+        assert!(clients.sweep_at == SWEEP_MIN);
+    }
+
+    #[test]
+    fn test_bucket_take_after_idling() {
+        let bucket = Bucket::new();
+
+        for _ in 0..3 {
+            bucket.take_at(RATE, NANOS);
+        }
+
+        // NOTE: however long it idles, it is never fuller than its burst
+        let result: Vec<bool> = (0..4).map(|_| bucket.take_at(RATE, 60 * NANOS)).collect();
+
+        assert!(result == [true, true, true, false]);
+    }
+}
@@ -0,0 +1,253 @@
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::time::{Duration, SystemTime};
+
+use crate::http::RequestMessage;
+use crate::utils::iso_date;
+
+/// Represents an access log format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+impl Format {
+    /// Parses a Format from its name.
+    pub fn parse(value: &str) -> Option<Format> {
+        match value {
+            "" | "text" => Some(Format::Text),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Represents a logging verbosity, from the least verbose to the most.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    /// Nothing but fatal startup errors.
+    Quiet,
+    /// Startup and shutdown lines, and access logs if enabled.
+    Info,
+    /// Everything above, and processing errors.
+    Debug,
+}
+
+impl Level {
+    /// Parses a Level from its name.
+    pub fn parse(value: &str) -> Option<Level> {
+        match value {
+            "quiet" => Some(Level::Quiet),
+            "" | "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+}
+
+/// Escapes given bytes as a JSON string, keeping the output ASCII-only.
+fn escape_json(value: &[u8]) -> String {
+    let mut escaped = String::with_capacity(value.len() + 2);
+
+    escaped.push('"');
+    for byte in value {
+        match byte {
+            b'"' => escaped.push_str("\\\""),
+            b'\\' => escaped.push_str("\\\\"),
+            0x20..=0x7e => escaped.push(*byte as char),
+            _ => escaped.push_str(&format!("\\u{byte:04x}")),
+        }
+    }
+    escaped.push('"');
+
+    escaped
+}
+
+/// Represents a single access log entry.
+pub struct Entry<'a> {
+    pub time: SystemTime,
+    pub remote: Option<IpAddr>,
+    pub method: &'a [u8],
+    pub path: &'a [u8],
+    pub status: u16,
+    pub bytes: usize,
+    pub elapsed: Duration,
+    pub id: &'a [u8],
+}
+
+impl Entry<'_> {
+    /// Returns the path to log, truncated to the parser limit, so a line stays bounded.
+    fn path(&self) -> &[u8] {
+        &self.path[..self.path.len().min(RequestMessage::PATH_LIMIT)]
+    }
+
+    /// Formats the Entry as a plain text line.
+    pub fn to_text(&self) -> String {
+        let remote = self.remote.map(|ip| ip.to_string());
+
+        format!(
+            "{} {} \"{} {}\" {} {} {}us {}",
+            iso_date(self.time),
+            remote.as_deref().unwrap_or("-"),
+            self.method.escape_ascii(),
+            self.path().escape_ascii(),
+            self.status,
+            self.bytes,
+            self.elapsed.as_micros(),
+            self.id.escape_ascii(),
+        )
+    }
+
+    /// Formats the Entry as a single-line JSON object.
+    pub fn to_json(&self) -> String {
+        let remote = self.remote.map(|ip| ip.to_string());
+
+        format!(
+            r#"{{"ts":"{}","remote":{},"method":{},"path":{},"status":{},"bytes":{},"us":{},"id":{}}}"#,
+            iso_date(self.time),
+            remote.map_or("null".into(), |ip| format!("\"{ip}\"")),
+            escape_json(self.method),
+            escape_json(self.path()),
+            self.status,
+            self.bytes,
+            self.elapsed.as_micros(),
+            escape_json(self.id),
+        )
+    }
+
+    /// Writes the Entry to stdout in a given Format, ignoring any errors.
+    pub fn write(&self, format: Format) {
+        let line = match format {
+            Format::Text => self.to_text(),
+            Format::Json => self.to_json(),
+        };
+
+        writeln!(io::stdout().lock(), "{line}").ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::time::UNIX_EPOCH;
+
+    use super::*;
+
+    #[test]
+    fn test_entry_to_text() {
+        let entry = Entry {
+            time: UNIX_EPOCH + Duration::from_secs(784111777),
+            remote: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            method: b"GET",
+            path: b"/\"quoted\"\x1b",
+            status: 404,
+            bytes: 151,
+            elapsed: Duration::from_micros(42),
+            id: b"0abc",
+        };
+
+        let result = entry.to_text();
+
+        assert!(
+            result == r#"1994-11-06T08:49:37Z 127.0.0.1 "GET /\"quoted\"\x1b" 404 151 42us 0abc"#
+        );
+    }
+
+    #[test]
+    fn test_entry_to_text_without_remote() {
+        let entry = Entry {
+            time: UNIX_EPOCH,
+            remote: None,
+            method: b"",
+            path: b"",
+            status: 400,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+            id: b"",
+        };
+
+        let result = entry.to_text();
+
+        assert!(result == r#"1970-01-01T00:00:00Z - " " 400 0 0us "#);
+    }
+
+    #[test]
+    fn test_entry_to_json() {
+        let entry = Entry {
+            time: UNIX_EPOCH + Duration::from_secs(784111777),
+            remote: Some(IpAddr::V4(Ipv4Addr::LOCALHOST)),
+            method: b"GET",
+            path: b"/\"quoted\"\\\r\n\xff",
+            status: 404,
+            bytes: 151,
+            elapsed: Duration::from_micros(42),
+            id: b"0abc",
+        };
+
+        let result = entry.to_json();
+
+        assert!(
+            result
+                == r#"{"ts":"1994-11-06T08:49:37Z","remote":"127.0.0.1","method":"GET","path":"/\"quoted\"\\\u000d\u000a\u00ff","status":404,"bytes":151,"us":42,"id":"0abc"}"#
+        );
+    }
+
+    #[test]
+    fn test_entry_to_json_without_remote() {
+        let entry = Entry {
+            time: UNIX_EPOCH,
+            remote: None,
+            method: b"",
+            path: b"",
+            status: 400,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+            id: b"",
+        };
+
+        let result = entry.to_json();
+
+        assert!(
+            result
+                == r#"{"ts":"1970-01-01T00:00:00Z","remote":null,"method":"","path":"","status":400,"bytes":0,"us":0,"id":""}"#
+        );
+    }
+
+    #[test]
+    fn test_entry_with_path_over_limit() {
+        let path = vec![b'x'; RequestMessage::PATH_LIMIT + 1];
+        let entry = Entry {
+            time: UNIX_EPOCH,
+            remote: None,
+            method: b"GET",
+            path: &path,
+            status: 414,
+            bytes: 0,
+            elapsed: Duration::ZERO,
+            id: b"",
+        };
+
+        for result in [entry.to_text(), entry.to_json()] {
+            assert!(result.matches('x').count() == RequestMessage::PATH_LIMIT);
+        }
+    }
+
+    #[test]
+    fn test_level_parse() {
+        assert!(Level::parse("quiet") == Some(Level::Quiet));
+        assert!(Level::parse("") == Some(Level::Info));
+        assert!(Level::parse("info") == Some(Level::Info));
+        assert!(Level::parse("debug") == Some(Level::Debug));
+        assert!(Level::parse("trace").is_none());
+        assert!(Level::Quiet < Level::Info && Level::Info < Level::Debug);
+    }
+
+    #[test]
+    fn test_format_parse() {
+        assert!(Format::parse("") == Some(Format::Text));
+        assert!(Format::parse("text") == Some(Format::Text));
+        assert!(Format::parse("json") == Some(Format::Json));
+        assert!(Format::parse("xml").is_none());
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use crate::state::{self, IN_FLIGHT};
+
+const CODES: usize = 500; // i.e. from 100 to 599
+const BUCKETS: [u64; 9] = [100, 250, 500, 1000, 2500, 5000, 10000, 100000, 1000000];
+
+static REQUESTS: [AtomicU64; CODES] = [const { AtomicU64::new(0) }; CODES];
+static LATENCY: [AtomicU64; BUCKETS.len()] = [const { AtomicU64::new(0) }; BUCKETS.len()];
+static LATENCY_SUM: AtomicU64 = AtomicU64::new(0);
+static LATENCY_COUNT: AtomicU64 = AtomicU64::new(0);
+static ACCEPTED: AtomicU64 = AtomicU64::new(0);
+static REJECTED: AtomicU64 = AtomicU64::new(0);
+
+/// Records a responded request with a given status code and latency.
+pub fn record(code: u16, elapsed: Duration) {
+    if let Some(counter) = REQUESTS.get(usize::from(code).wrapping_sub(100)) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    let micros = u64::try_from(elapsed.as_micros()).unwrap_or(u64::MAX);
+
+    // NOTE: buckets are not cumulative here, they are summed up when rendered
+    if let Some(index) = BUCKETS.iter().position(|bucket| micros <= *bucket) {
+        LATENCY[index].fetch_add(1, Ordering::Relaxed);
+    }
+    LATENCY_SUM.fetch_add(micros, Ordering::Relaxed);
+    LATENCY_COUNT.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records an accepted connection, whether it is processed or rejected afterwards.
+pub fn accept() {
+    ACCEPTED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records a connection closed right away for being over the limit.
+pub fn reject() {
+    REJECTED.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Renders all metrics in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut text = String::new();
+
+    text.push_str("# HELP http_requests_total Total number of responded requests.\n");
+    text.push_str("# TYPE http_requests_total counter\n");
+    for (index, counter) in REQUESTS.iter().enumerate() {
+        let count = counter.load(Ordering::Relaxed);
+
+        if count > 0 {
+            writeln!(
+                text,
+                "http_requests_total{{code=\"{}\"}} {count}",
+                index + 100
+            )
+            .ok();
+        }
+    }
+
+    text.push_str("# HELP http_request_duration_seconds Time spent responding to requests.\n");
+    text.push_str("# TYPE http_request_duration_seconds histogram\n");
+    let mut cumulative = 0;
+    for (bucket, counter) in BUCKETS.iter().zip(&LATENCY) {
+        cumulative += counter.load(Ordering::Relaxed);
+        writeln!(
+            text,
+            "http_request_duration_seconds_bucket{{le=\"{}\"}} {cumulative}",
+            *bucket as f64 / 1e6,
+        )
+        .ok();
+    }
+
+    let count = LATENCY_COUNT.load(Ordering::Relaxed);
+    let sum = LATENCY_SUM.load(Ordering::Relaxed) as f64 / 1e6;
+    writeln!(
+        text,
+        "http_request_duration_seconds_bucket{{le=\"+Inf\"}} {count}"
+    )
+    .ok();
+    writeln!(text, "http_request_duration_seconds_sum {sum}").ok();
+    writeln!(text, "http_request_duration_seconds_count {count}").ok();
+
+    text.push_str("# HELP http_connections_accepted_total Total number of accepted connections.\n");
+    text.push_str("# TYPE http_connections_accepted_total counter\n");
+    writeln!(
+        text,
+        "http_connections_accepted_total {}",
+        ACCEPTED.load(Ordering::Relaxed)
+    )
+    .ok();
+
+    text.push_str("# HELP http_connections_in_flight Number of connections being processed.\n");
+    text.push_str("# TYPE http_connections_in_flight gauge\n");
+    writeln!(
+        text,
+        "http_connections_in_flight {}",
+        IN_FLIGHT.load(Ordering::Relaxed)
+    )
+    .ok();
+
+    text.push_str(
+        "# HELP http_connections_rejected_total Connections closed for being over the limit.\n",
+    );
+    text.push_str("# TYPE http_connections_rejected_total counter\n");
+    writeln!(
+        text,
+        "http_connections_rejected_total {}",
+        REJECTED.load(Ordering::Relaxed)
+    )
+    .ok();
+
+    text
+}
+
+/// Counts responded requests per status class, i.e. 1xx to 5xx.
+fn classes() -> [u64; 5] {
+    let mut classes = [0; 5];
+
+    for (index, counter) in REQUESTS.iter().enumerate() {
+        classes[index / 100] += counter.load(Ordering::Relaxed);
+    }
+
+    classes
+}
+
+/// Summarizes the counters in a single line, e.g. to dump them on demand.
+pub fn summary() -> String {
+    let classes = classes();
+
+    format!(
+        "connections={} rejected={} in_flight={} requests={} 1xx={} 2xx={} 3xx={} 4xx={} 5xx={}",
+        ACCEPTED.load(Ordering::Relaxed),
+        REJECTED.load(Ordering::Relaxed),
+        IN_FLIGHT.load(Ordering::Relaxed),
+        classes.iter().sum::<u64>(),
+        classes[0],
+        classes[1],
+        classes[2],
+        classes[3],
+        classes[4],
+    )
+}
+
+/// Renders a few counters as plain text, one per line, for people rather than scrapers.
+pub fn stats() -> String {
+    let classes = classes();
+    let mut text = String::new();
+
+    writeln!(text, "uptime_seconds: {}", state::uptime().as_secs()).ok();
+    writeln!(text, "requests: {}", classes.iter().sum::<u64>()).ok();
+    for (index, count) in classes.iter().enumerate() {
+        writeln!(text, "requests_{}xx: {count}", index + 1).ok();
+    }
+    writeln!(text, "in_flight: {}", IN_FLIGHT.load(Ordering::Relaxed)).ok();
+
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        record(599, Duration::from_micros(200));
+        record(599, Duration::from_secs(5));
+
+        let result = render();
+
+        assert!(result.contains("# TYPE http_requests_total counter\n"));
+        assert!(result.contains("# TYPE http_request_duration_seconds histogram\n"));
+        assert!(result.contains("http_requests_total{code=\"599\"} 2\n"));
+        assert!(result.contains("http_request_duration_seconds_bucket{le=\"0.0001\"} "));
+        assert!(result.contains("http_request_duration_seconds_bucket{le=\"1\"} "));
+        assert!(result.contains("http_request_duration_seconds_bucket{le=\"+Inf\"} "));
+        assert!(result.contains("http_request_duration_seconds_count "));
+    }
+
+    #[test]
+    fn test_render_connections() {
+        reject();
+
+        let result = render();
+
+        assert!(result.contains("# TYPE http_connections_in_flight gauge\n"));
+        assert!(result.contains("http_connections_in_flight "));
+        assert!(!result.contains("http_connections_rejected_total 0\n"));
+    }
+
+    #[test]
+    fn test_summary() {
+        accept();
+        record(100, Duration::ZERO);
+
+        let result = summary();
+
+        assert!(result.starts_with("connections="));
+        assert!(!result.contains("connections=0 "));
+        assert!(!result.contains(" 1xx=0 "));
+        assert!(result.contains(" 5xx="));
+    }
+
+    #[test]
+    fn test_stats() {
+        record(204, Duration::ZERO);
+        record(404, Duration::ZERO);
+        record(404, Duration::ZERO);
+
+        let result = stats();
+
+        assert!(result.starts_with("uptime_seconds: "));
+        assert!(result.contains("\nrequests: "));
+        assert!(!result.contains("\nrequests: 0\n"));
+        assert!(!result.contains("\nrequests_2xx: 0\n"));
+        assert!(!result.contains("\nrequests_4xx: 0\n"));
+        assert!(result.contains("\nrequests_5xx: "));
+        assert!(result.contains("\nin_flight: "));
+    }
+
+    #[test]
+    fn test_record_out_of_range() {
+        record(99, Duration::ZERO);
+        record(600, Duration::ZERO);
+
+        let result = render();
+
+        assert!(!result.contains("code=\"99\""));
+        assert!(!result.contains("code=\"600\""));
+    }
+}
@@ -0,0 +1,2159 @@
+use std::borrow::Cow;
+use std::fs::{self, Permissions};
+use std::future;
+use std::io::{ErrorKind, IoSlice};
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::pin::Pin;
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use async_std::io::{self, BufReader, Read, Write, WriteExt};
+use async_std::net::{TcpListener, TcpStream};
+use async_std::os::unix::net::{UnixListener, UnixStream};
+use async_std::prelude::*;
+use async_std::task;
+use socket2::{Domain, Protocol, SockRef, Socket, TcpKeepalive, Type};
+
+use crate::config::{Config, Shared};
+use crate::http::{
+    Body, RequestMessage, ResponseMessage, RESP_100, RESP_400, RESP_408, RESP_413, RESP_414,
+    RESP_429, RESP_431, RESP_501,
+};
+use crate::limit;
+use crate::log::{Entry, Level};
+use crate::metrics;
+use crate::state::{self, Flight, IN_FLIGHT, READY, SHUTDOWN};
+use crate::utils::{
+    drain, extract, gzip, is_crlf_terminated, read_proxy, request_id, write_all_vectored,
+    write_http_date, Head,
+};
+use crate::CRLF;
+
+const GZIP: &[u8] = b"Content-Encoding: gzip";
+const VARY: &[u8] = b"Vary: Accept-Encoding";
+const CLOSE: &[u8] = b"Connection: close";
+const KEEP_ALIVE: &[u8] = b"Connection: keep-alive";
+const RETRY_LIMITED: &[u8] = b"Retry-After: 1"; // a rate is at least a request per second
+const TICK_STEP: Duration = Duration::from_millis(500);
+const BIND_DELAY: Duration = Duration::from_millis(100);
+const BIND_DELAY_MAX: Duration = Duration::from_secs(5);
+
+// NOTE: smaller bodies barely shrink, if at all, with the gzip framing overhead
+const COMPRESS_MIN_BYTES: usize = 1024;
+
+// NOTE: smaller bodies are cheaper to copy after the head than to hand over on their own, as
+// the `write` benchmark group shows, with the two crossing between 4 and 8 KiB
+const VECTORED_MIN_BYTES: usize = 8192;
+
+/// Serializes a ResponseMessage to a given RequestMessage into a buffer, along with the extra
+/// headers a given function appends to it.
+///
+/// A body large enough is left out, and returned to be written along with the buffer in a single
+/// vectored write, instead of being copied. Responses to HEAD requests keep the same headers,
+/// including `Content-Length`, but no body.
+fn serialize<'a>(
+    request: &RequestMessage,
+    response: &'a ResponseMessage,
+    extra: impl FnOnce(&mut Vec<u8>),
+    bytes: &mut Vec<u8>,
+) -> &'a [u8] {
+    response.write_fields(bytes);
+    extra(bytes);
+    bytes.extend_from_slice(CRLF);
+
+    if request.method == b"HEAD" {
+        return &[];
+    } else if response.body.len() >= VECTORED_MIN_BYTES {
+        return &response.body;
+    }
+    bytes.extend_from_slice(&response.body);
+
+    &[]
+}
+
+/// Appends a header made of given parts to a given buffer.
+fn push(bytes: &mut Vec<u8>, parts: &[&[u8]]) {
+    for part in parts {
+        bytes.extend_from_slice(part);
+    }
+    bytes.extend_from_slice(CRLF);
+}
+
+/// Checks if a RequestMessage from a given client is over a rate limit, if any, taking tokens
+/// out otherwise, first from the client bucket, and then from the global one.
+///
+/// A request refused by the global limit gets its client token back, so a global overload does
+/// not use up the allowance of every client as well. Health checks are never limited, so a flood
+/// does not get an instance restarted on top.
+fn is_limited(request: &RequestMessage, client: Option<IpAddr>, config: &Config) -> bool {
+    if request.is_probe(config) {
+        return false;
+    }
+
+    let per_ip = match (config.per_ip_limit, client) {
+        (Some(rate), Some(client)) => Some((rate, client)),
+        _ => None,
+    };
+
+    if per_ip.is_some_and(|(rate, client)| !limit::take_for(client, rate)) {
+        return true;
+    }
+
+    // NOTE: the global bucket is taken from last, so a client over its own limit spends none of it
+    let global = config
+        .rate_limit
+        .is_some_and(|rate| !limit::GLOBAL.take(rate));
+
+    if let (true, Some((rate, client))) = (global, per_ip) {
+        limit::refund_for(client, rate);
+    }
+
+    global
+}
+
+/// Processes stream bytes as HTTP request messages, and responds accordingly.
+///
+/// It is a single connection from a given remote, if any, kept alive for as long as it may be.
+pub async fn process<S>(
+    stream: S,
+    remote: Option<IpAddr>,
+    config: Arc<Config>,
+) -> Result<(), std::io::Error>
+where
+    S: Read + Write + Unpin,
+{
+    let mut reader = BufReader::with_capacity(config.read_buffer_bytes, stream);
+    let line_limit = RequestMessage::line_limit(config.max_uri_bytes);
+    let mut buffer: Vec<u8> = Vec::with_capacity(line_limit);
+    let mut output: Vec<u8> = Vec::with_capacity(config.read_buffer_bytes);
+    let limits = (line_limit, config.max_header_bytes);
+    let mut count = 0;
+
+    loop {
+        buffer.clear();
+
+        // NOTE: the first request has to arrive in time, the next ones may idle for a while
+        let first = count == 0;
+        let timeout = if first {
+            config.read_timeout
+        } else {
+            config.keepalive
+        };
+        let read = extract(&mut reader, &mut buffer, limits);
+
+        let head = match io::timeout(timeout, read).await {
+            Ok(_) if !first && buffer.is_empty() => break, // the client has gone away
+            Ok(head) => Some(head),
+            Err(err) if err.kind() == ErrorKind::TimedOut && buffer.is_empty() => break,
+            Err(err) if err.kind() == ErrorKind::TimedOut => None, // a stalled request
+            Err(err) => return Err(err),
+        };
+        count += 1;
+
+        let (time, start) = (SystemTime::now(), Instant::now());
+        let request = RequestMessage::from(buffer.as_slice());
+
+        // NOTE: the header is anyone's to set, unless there is a proxy overwriting it
+        let client = if config.trust_proxy {
+            request.forwarded_for().or(remote)
+        } else {
+            remote
+        };
+        // NOTE: the left-most address is whatever the client says, the proxy only appends to it
+        let peer = if config.trust_proxy {
+            request.forwarded_last().or(remote)
+        } else {
+            remote
+        };
+        let body = match request.body() {
+            Body::Sized(length) if length > config.max_body_bytes => None,
+            body => Some(body),
+        };
+        let mut response = match (head, body) {
+            (None, _) => RESP_408,
+            (Some(Head::Truncated), _) => RESP_414, // whatever has been cut, it is too long
+            (Some(Head::Overflowed), _) => RESP_431,
+            (Some(Head::Rejected), _) => RESP_400,
+            // NOTE: a proxy in front may see lines where there are none, or the other way round
+            _ if config.strict_line_endings && !is_crlf_terminated(&buffer) => RESP_400,
+            (_, None) => RESP_413,
+            (_, Some(Body::Coded)) => RESP_501,
+            (_, Some(Body::Malformed)) => RESP_400,
+            _ if is_limited(&request, peer, &config) => RESP_429,
+            _ => request.response(&config),
+        };
+
+        // NOTE: the body is of no use, but has to be read past to get to the next request
+        let drained = match (head, body) {
+            // NOTE: a client waiting for a go-ahead gets a final status right away when it is
+            // not told to go ahead, and may then never send the body, so it is not waited for
+            (Some(Head::Complete), Some(Body::Sized(length)))
+                if length > 0
+                    && request.is_continue_expected()
+                    && !(200..400).contains(&response.code) =>
+            {
+                false
+            }
+            (Some(Head::Complete), Some(Body::Sized(length))) if length > 0 => {
+                // NOTE: otherwise the client would wait for a go-ahead until it times out
+                if request.is_continue_expected() {
+                    let writer = reader.get_mut();
+                    writer.write_all(&RESP_100.to_bytes(&[])).await?;
+                    writer.flush().await?;
+                }
+
+                let drain = drain(&mut reader, length);
+
+                match io::timeout(config.read_timeout, drain).await {
+                    Ok(drained) => drained,
+                    Err(err) if err.kind() == ErrorKind::TimedOut => false,
+                    Err(err) => return Err(err),
+                }
+            }
+            (_, body) => matches!(body, Some(Body::Sized(_))),
+        };
+
+        // NOTE: a malformed request leaves us unsure where the next one begins
+        let keep_alive = head == Some(Head::Complete)
+            && drained
+            && !config.keepalive.is_zero()
+            && count < config.keepalive_max
+            && !SHUTDOWN.load(Ordering::Relaxed)
+            && request.is_persistent()
+            && !matches!(response.code, 400 | 414 | 431 | 505);
+
+        // NOTE: a client having the body already is not sent it again
+        if let Some(not_modified) = request.not_modified(&response) {
+            response = not_modified;
+        }
+
+        // NOTE: whether a response is compressed depends on the request, so caches must know
+        let compressible = config.compress && response.body.len() >= COMPRESS_MIN_BYTES;
+        let compressed = compressible && request.is_gzip_accepted();
+
+        if compressed {
+            response.body = Cow::Owned(gzip(&response.body));
+        }
+
+        // NOTE: the location depends on the request, so it cannot be a part of the response
+        let location = (response.code == 308)
+            .then(|| request.redirect(&config))
+            .flatten();
+
+        let generated = request_id();
+        let id = request.request_id().unwrap_or(&generated);
+
+        // NOTE: the headers are written straight into the output buffer, so none is allocated
+        let extra = |bytes: &mut Vec<u8>| {
+            push(bytes, &[if keep_alive { KEEP_ALIVE } else { CLOSE }]);
+            bytes.extend_from_slice(b"Date: ");
+            write_http_date(bytes, time);
+            bytes.extend_from_slice(CRLF);
+            push(bytes, &[b"X-Request-Id: ", id]);
+
+            if let Some(location) = &location {
+                push(bytes, &[location]);
+            }
+            // NOTE: a compressed body is not the same bytes, but it is the same content
+            if let Some(etag) = response.etag.as_deref() {
+                push(
+                    bytes,
+                    &[if compressed { b"ETag: W/" } else { b"ETag: " }, etag],
+                );
+            }
+            if let Some(server) = &config.server {
+                push(bytes, &[server]);
+            }
+
+            if let Some(cors_origin) = &config.cors_origin {
+                push(bytes, &[cors_origin]);
+            }
+
+            // NOTE: load balancers back off better when told for how long
+            if let (503, Some(retry_after)) = (response.code, &config.retry_after) {
+                push(bytes, &[retry_after]);
+            } else if response.code == 429 {
+                push(bytes, &[RETRY_LIMITED]);
+            }
+            if compressed {
+                push(bytes, &[GZIP]);
+            }
+            if compressible {
+                push(bytes, &[VARY]);
+            }
+            for header in &config.extra_headers {
+                push(bytes, &[header]);
+            }
+        };
+
+        // NOTE: a deliberate slowness for testing timeouts, kept off the health checks
+        if !config.response_delay.is_zero() && !request.is_probe(&config) {
+            state::sleep(config.response_delay).await;
+        }
+
+        // NOTE: the reader is not read from while writing, so it is fine to write past it
+        // NOTE: both buffers live as long as the connection, so keep-alive requests reuse them
+        output.clear();
+        let body = serialize(&request, &response, extra, &mut output);
+        let mut slices = [IoSlice::new(&output), IoSlice::new(body)];
+
+        let writer = reader.get_mut();
+        write_all_vectored(writer, &mut slices).await?;
+        writer.flush().await?;
+
+        let elapsed = start.elapsed();
+        metrics::record(response.code, elapsed);
+
+        if config.access_log && config.log_level >= Level::Info && !request.is_stats(&config) {
+            Entry {
+                time,
+                remote: client,
+                method: request.method,
+                path: request.path,
+                status: response.code,
+                bytes: output.len() + body.len(),
+                elapsed,
+                id,
+            }
+            .write(config.log_format);
+        }
+
+        if !keep_alive {
+            break;
+        }
+    }
+
+    // NOTE: closing shuts down the write half, or sends a TLS close notify first
+    let writer = reader.get_mut();
+    future::poll_fn(|cx| Pin::new(&mut *writer).poll_close(cx)).await?;
+
+    Ok(())
+}
+
+/// Represents an accepted connection over any transport.
+trait Connection: Read + Write + Unpin + Send + 'static {
+    /// Returns the IP address of a remote peer, if there is one.
+    fn remote(&self) -> Option<IpAddr>;
+
+    /// Tunes the underlying socket right after accepting it, according to a given Config.
+    fn tune(&self, _config: &Config) {}
+}
+
+impl Connection for TcpStream {
+    fn remote(&self) -> Option<IpAddr> {
+        self.peer_addr().ok().map(|addr| addr.ip())
+    }
+
+    fn tune(&self, config: &Config) {
+        self.set_nodelay(config.nodelay).ok(); // we do not really care if it clicks or not
+
+        if let Some(period) = config.tcp_keepalive {
+            SockRef::from(self)
+                .set_tcp_keepalive(&keepalive(period))
+                .ok();
+        }
+    }
+}
+
+impl Connection for UnixStream {
+    fn remote(&self) -> Option<IpAddr> {
+        None
+    }
+}
+
+/// Returns TCP keep-alive parameters probing an idle connection every given period.
+///
+/// The idle time before the first probe is set everywhere (`TCP_KEEPIDLE` on Linux, and
+/// `TCP_KEEPALIVE` on macOS), while the interval between probes is only set where it can be,
+/// with a system default elsewhere (e.g. 75 seconds on OpenBSD). The number of probes before a
+/// connection is dropped is always left to the system (e.g. `net.ipv4.tcp_keepalive_probes`).
+fn keepalive(period: Duration) -> TcpKeepalive {
+    let keepalive = TcpKeepalive::new().with_time(period);
+
+    #[cfg(any(
+        target_os = "android",
+        target_os = "freebsd",
+        target_os = "ios",
+        target_os = "linux",
+        target_os = "macos",
+        target_os = "netbsd",
+    ))]
+    let keepalive = keepalive.with_interval(period);
+
+    keepalive
+}
+
+/// Checks if a given error is a client having gone away, e.g. before reading a response.
+///
+/// The stream is dropped either way, so it only matters for how loudly the error is logged.
+fn is_disconnect(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        ErrorKind::BrokenPipe
+            | ErrorKind::ConnectionReset
+            | ErrorKind::ConnectionAborted
+            | ErrorKind::UnexpectedEof
+    )
+}
+
+/// Handles a stream as is, or as a TLS one if configured so.
+async fn handle<S: Connection>(mut stream: S, config: Arc<Config>) -> Result<(), std::io::Error> {
+    let mut remote = stream.remote();
+
+    // NOTE: the header comes before anything else, a TLS handshake included
+    if config.proxy_protocol {
+        let read = read_proxy(&mut stream);
+        remote = io::timeout(config.read_timeout, read).await?.or(remote);
+    }
+
+    match &config.tls {
+        Some(acceptor) => {
+            let accept = acceptor.accept(stream);
+            let stream = io::timeout(config.read_timeout, accept).await?;
+            process(stream, remote, config.clone()).await
+        }
+        None => process(stream, remote, config).await,
+    }
+}
+
+/// Accepts incoming connections, and spawns a task to process each of them.
+///
+/// Each connection is processed with the configuration current at the moment it is accepted.
+async fn serve<I, S>(mut incoming: I, shared: Arc<Shared>)
+where
+    I: Stream<Item = io::Result<S>> + Unpin,
+    S: Connection,
+{
+    loop {
+        state::tick();
+
+        // NOTE: waking up now and then keeps the watchdog from mistaking idleness for a hang
+        let next = io::timeout(TICK_STEP, async { Ok(incoming.next().await) });
+        let stream = match next.await {
+            Ok(Some(Ok(stream))) => stream,
+            Ok(None) => break,
+            Ok(Some(Err(_))) | Err(_) => continue,
+        };
+        metrics::accept();
+
+        let config = shared.load();
+
+        // NOTE: closing right away is cheaper than queueing up behind a flood
+        if IN_FLIGHT.load(Ordering::Relaxed) >= config.max_connections {
+            metrics::reject();
+            continue;
+        }
+        stream.tune(&config);
+
+        let (log_errors, log_level) = (config.log_errors, config.log_level);
+        let flight = Flight::start();
+
+        task::spawn(async move {
+            let _flight = flight;
+
+            // NOTE: clients going away is business as usual, so it is only worth it for debugging
+            let Err(err) = handle(stream, config).await else {
+                return;
+            };
+
+            if is_disconnect(&err) {
+                if log_level >= Level::Debug {
+                    eprintln!("Client disconnected: {err}");
+                }
+            } else if log_errors {
+                eprintln!("Processing error: {err}");
+            }
+        });
+    }
+}
+
+/// Watches the accept loops for hangs, warning about them, and quitting if configured so.
+///
+/// It runs in a thread of its own, so it keeps going even if every task is stuck.
+fn watch(shared: Arc<Shared>) {
+    let mut stalled = false;
+
+    loop {
+        thread::sleep(Duration::from_secs(1));
+
+        let config = shared.load();
+        let Some(limit) = config.watchdog else {
+            continue;
+        };
+
+        // NOTE: warning once per hang is enough, it is either going to recover or not
+        if !state::is_stalled(limit) {
+            stalled = false;
+        } else if !stalled {
+            stalled = true;
+            eprintln!(
+                "Accept loops have not gone around for over {}s",
+                limit.as_secs()
+            );
+
+            if config.watchdog_exit {
+                eprintln!("Watchdog timeout exceeded; Quitting");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+/// Binds a TCP listener to a given address, and returns it along with the address bound to.
+///
+/// The two addresses differ when the port is zero, i.e. picked by the OS.
+fn bind(addr: SocketAddr, config: &Config) -> io::Result<(TcpListener, SocketAddr)> {
+    let socket = Socket::new(Domain::for_address(addr), Type::STREAM, Some(Protocol::TCP))?;
+
+    // NOTE: otherwise a quick restart may fail while the old socket lingers in TIME_WAIT
+    socket.set_reuse_address(config.reuse_addr)?;
+    socket.set_reuse_port(config.reuse_port || config.workers > 1)?;
+    socket.bind(&addr.into())?;
+    socket.listen(config.backlog)?;
+    socket.set_nonblocking(true)?;
+
+    let listener = TcpListener::from(std::net::TcpListener::from(socket));
+    let local = listener.local_addr()?;
+
+    Ok((listener, local))
+}
+
+/// Binds a TCP listener like `bind` does, retrying up to BIND_RETRIES times with an exponential
+/// backoff, e.g. while a previous process still holds the port during a rolling restart.
+async fn bind_with_retries(
+    addr: SocketAddr,
+    config: &Config,
+) -> io::Result<(TcpListener, SocketAddr)> {
+    let mut delay = BIND_DELAY;
+    let mut attempt = 0;
+
+    loop {
+        match bind(addr, config) {
+            Err(err) if attempt < config.bind_retries => {
+                attempt += 1;
+                if config.log_level >= Level::Info {
+                    eprintln!(
+                        "Cannot listen on {addr}: {err}; Retrying in {}ms ({attempt}/{})",
+                        delay.as_millis(),
+                        config.bind_retries
+                    );
+                }
+                task::sleep(delay).await;
+                delay = (delay * 2).min(BIND_DELAY_MAX);
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Reloads the configuration from the environment, keeping the current one if it is invalid.
+///
+/// The listening settings cannot change without dropping the listeners, so they stay as they are.
+fn reload(shared: &Shared) {
+    let current = shared.load();
+
+    let mut config = match Config::from_env() {
+        Ok(config) => config,
+        Err(err) => {
+            eprintln!("{err}; Keeping the current configuration");
+            return;
+        }
+    };
+
+    let listening = (config.addr, &config.unix, config.workers, config.backlog);
+    let reusing = (config.reuse_addr, config.reuse_port);
+
+    if config.log_level >= Level::Info {
+        if listening
+            != (
+                current.addr,
+                &current.unix,
+                current.workers,
+                current.backlog,
+            )
+            || reusing != (current.reuse_addr, current.reuse_port)
+        {
+            println!("Ignoring changed listening settings until restart");
+        }
+        println!("Reloaded configuration");
+    }
+
+    config.addr = current.addr;
+    config.unix = current.unix.clone();
+    config.workers = current.workers;
+    config.backlog = current.backlog;
+    config.reuse_addr = current.reuse_addr;
+    config.reuse_port = current.reuse_port;
+
+    shared.store(config);
+}
+
+/// Runs a canned health check request through parsing and responding, without networking.
+///
+/// Returns an exit code, i.e. zero if the response is as expected, and non-zero otherwise.
+pub fn check(config: &Config) -> i32 {
+    let bytes = [b"GET ", config.health.as_slice(), b" HTTP/1.1\r\n\r\n"].concat();
+    let response = RequestMessage::from(bytes.as_slice()).response(config);
+
+    println!("{} {}", response.code, response.desc.escape_ascii());
+
+    let expected = if config.health_json { 200 } else { 204 };
+
+    i32::from(response.code != expected)
+}
+
+/// Listens according to a given Config, and serves until a shutdown signal comes.
+///
+/// Signals come as numbers, e.g. from `async-signals`, so they can be set up before anything
+/// else: SIGHUP reloads the configuration, SIGUSR1 dumps the counters, and any other one shuts
+/// the server down.
+pub async fn run<S>(config: Config, mut signals: S)
+where
+    S: Stream<Item = i32> + Unpin,
+{
+    let shared = Arc::new(Shared::new(config));
+    let config = shared.load();
+
+    let verbose = config.log_level >= Level::Info;
+    let scheme = if config.tls.is_some() {
+        "https"
+    } else {
+        "http"
+    };
+
+    let servers: Vec<_> = match &config.unix {
+        Some(path) => {
+            // NOTE: a socket left behind by a killed process would fail the bind
+            if fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+                fs::remove_file(path).ok();
+            }
+
+            let listener = match UnixListener::bind(path).await {
+                Ok(listener) => {
+                    if verbose {
+                        println!("Listening on {} ({scheme})", path.display());
+                    }
+                    listener
+                }
+                Err(ref err) => {
+                    eprintln!("Cannot listen on {}: {err}", path.display());
+                    return;
+                }
+            };
+
+            // NOTE: whoever can reach the directory may connect, whatever their UID is
+            fs::set_permissions(path, Permissions::from_mode(0o666)).ok();
+
+            let shared = shared.clone();
+            vec![task::spawn(async move {
+                serve(listener.incoming(), shared).await
+            })]
+        }
+        None => {
+            let mut addr = config.addr;
+            let mut listeners = Vec::with_capacity(config.workers);
+
+            // NOTE: each worker has a listener of its own, the kernel spreads connections
+            for _ in 0..config.workers {
+                match bind_with_retries(addr, &config).await {
+                    Ok((listener, local)) => {
+                        addr = local; // so a port picked by the OS is shared by all of them
+                        listeners.push(listener);
+                    }
+                    Err(ref err) => {
+                        eprintln!("Cannot listen on {addr}: {err}");
+                        return;
+                    }
+                }
+            }
+
+            if verbose {
+                match config.workers {
+                    1 => println!("Listening on {addr} ({scheme})"),
+                    count => println!("Listening on {addr} ({scheme}, {count} workers)"),
+                }
+            }
+            if config.log_level >= Level::Debug {
+                println!("Using TCP_NODELAY={}", u8::from(config.nodelay));
+                println!("Using LISTEN_BACKLOG={}", config.backlog);
+                match config.tcp_keepalive {
+                    Some(period) => println!("Using TCP_KEEPALIVE_SECS={}", period.as_secs()),
+                    None => println!("Using TCP_KEEPALIVE_SECS=0"),
+                }
+            }
+
+            listeners
+                .into_iter()
+                .map(|listener| {
+                    let shared = shared.clone();
+                    task::spawn(async move { serve(listener.incoming(), shared).await })
+                })
+                .collect()
+        }
+    };
+
+    // NOTE: a thread waking up every second is not worth it for a watchdog that is off
+    if config.watchdog.is_some() {
+        let watched = shared.clone();
+        thread::spawn(move || watch(watched));
+    }
+
+    // NOTE: SIGHUP reloads, SIGUSR1 dumps the counters, every other signal shuts down
+    while let Some(signal) = signals.next().await {
+        match signal {
+            1 => reload(&shared),
+            10 => println!("{}", metrics::summary()),
+            _ => break,
+        }
+    }
+
+    let config = shared.load();
+    let verbose = config.log_level >= Level::Info;
+
+    READY.store(false, Ordering::Relaxed);
+    SHUTDOWN.store(true, Ordering::Relaxed);
+
+    // NOTE: cancelling the accept loops drops the listeners, so no new connections come in
+    for server in servers {
+        server.cancel().await;
+    }
+
+    if let Some(path) = &config.unix {
+        fs::remove_file(path).ok();
+    }
+
+    let deadline = Instant::now() + config.shutdown;
+
+    if verbose {
+        println!(
+            "Draining {} in-flight connections, timeout {}s",
+            IN_FLIGHT.load(Ordering::Relaxed),
+            config.shutdown.as_secs()
+        );
+    }
+
+    while IN_FLIGHT.load(Ordering::Relaxed) > 0 && Instant::now() < deadline {
+        task::sleep(Duration::from_millis(10)).await;
+    }
+
+    if verbose {
+        match IN_FLIGHT.load(Ordering::Relaxed) {
+            0 => println!("Drained; Quitting"),
+            count => println!("Timeout exceeded, abandoning {count} connections; Quitting"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+    use std::path::Path;
+    use std::task::{Context, Poll};
+
+    use async_std::io::{Cursor, ReadExt};
+
+    use super::*;
+    use crate::http::RESP_404;
+    use crate::limit::Rate;
+    use crate::CRLF;
+
+    const BODY: &[u8] = b"<h1>404 Not Found</h1>";
+    const LIMIT: usize = RequestMessage::line_limit(RequestMessage::PATH_LIMIT);
+
+    /// Represents an in-memory stream, reading from a given input and writing to an output.
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Duplex {
+        fn new(input: &[u8]) -> Duplex {
+            Duplex {
+                input: Cursor::new(input.to_vec()),
+                output: Vec::new(),
+            }
+        }
+    }
+
+    impl Read for Duplex {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.input).poll_read(cx, buf)
+        }
+    }
+
+    impl Write for Duplex {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.output.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Processes given request bytes with a given Config, and returns the response bytes.
+    ///
+    /// The `Date` and `X-Request-Id` headers differ from run to run, so they are left out.
+    fn roundtrip_with(config: Config, request: &[u8]) -> Vec<u8> {
+        let mut stream = Duplex::new(request);
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let lines: Vec<&[u8]> = stream
+            .output
+            .split_inclusive(|char| char == &CRLF[1])
+            .filter(|line| !line.starts_with(b"Date: ") && !line.starts_with(b"X-Request-Id: "))
+            .collect();
+
+        lines.concat()
+    }
+
+    /// Processes given request bytes with the default Config, and returns the response bytes.
+    fn roundtrip(request: &[u8]) -> Vec<u8> {
+        roundtrip_with(Config::default(), request)
+    }
+
+    #[test]
+    fn test_roundtrip_200() {
+        let config = Config {
+            root: Some(ResponseMessage {
+                body: Cow::Borrowed(b"Hello"),
+                ..ResponseMessage::from_code(200)
+            }),
+            ..Config::default()
+        };
+
+        let result = roundtrip_with(config, b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+        assert!(
+            result
+                == b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\
+                     Server: 404-container\r\n\r\nHello"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_404() {
+        let result = roundtrip(b"GET /foo HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+        assert!(
+            result
+                == b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\
+                     Server: 404-container\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_304() {
+        let config = || Config {
+            root: Some(ResponseMessage {
+                body: Cow::Borrowed(b"Hello"),
+                etag: Some(Cow::Borrowed(b"\"5-f7d18982\"")),
+                ..ResponseMessage::from_code(200)
+            }),
+            ..Config::default()
+        };
+
+        let fresh = roundtrip_with(config(), b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let cached = roundtrip_with(
+            config(),
+            b"GET / HTTP/1.1\r\nIf-None-Match: \"x\", W/\"5-f7d18982\"\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(
+            fresh
+                == b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\nConnection: close\r\n\
+                     ETag: \"5-f7d18982\"\r\nServer: 404-container\r\n\r\nHello"
+        );
+        assert!(
+            cached
+                == b"HTTP/1.1 304 Not Modified\r\nConnection: close\r\n\
+                     ETag: \"5-f7d18982\"\r\nServer: 404-container\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_404_with_if_none_match() {
+        let config = Config {
+            default: ResponseMessage {
+                body: Cow::Borrowed(b"Nope"),
+                etag: Some(Cow::Borrowed(b"\"4-x\"")),
+                ..RESP_404
+            },
+            ..Config::default()
+        };
+
+        let result = roundtrip_with(
+            config,
+            b"GET /foo HTTP/1.1\r\nIf-None-Match: *\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(result.starts_with(b"HTTP/1.1 404 Not Found\r\nContent-Length: 4\r\n"));
+        assert!(result.ends_with(b"\r\n\r\nNope"));
+    }
+
+    #[test]
+    fn test_roundtrip_405() {
+        let result = roundtrip(b"CONNECT example.com:443 HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+        assert!(
+            result
+                == b"HTTP/1.1 405 Method Not Allowed\r\n\
+                     Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH\r\n\
+                     Content-Length: 0\r\nConnection: close\r\nServer: 404-container\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_414() {
+        let config = Config {
+            max_uri_bytes: 4,
+            ..Config::default()
+        };
+
+        let result = roundtrip_with(config, b"GET /foo/bar HTTP/1.1\r\n\r\n");
+
+        assert!(
+            result
+                == b"HTTP/1.1 414 URI Too Long\r\nContent-Length: 0\r\nConnection: close\r\n\
+                     Server: 404-container\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_505() {
+        let result = roundtrip(b"GET / HTTP/2.0\r\n\r\n");
+
+        assert!(
+            result
+                == b"HTTP/1.1 505 HTTP Version Not Supported\r\nContent-Length: 0\r\n\
+                     Connection: close\r\nServer: 404-container\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_400() {
+        let result = roundtrip(b"GET /\x7f HTTP/1.1\r\n\r\n");
+
+        assert!(
+            result
+                == b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\nConnection: close\r\n\
+                     Server: 404-container\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_roundtrip_health_json() {
+        let config = Config {
+            health_json: true,
+            ..Config::default()
+        };
+
+        let result = roundtrip_with(
+            config,
+            b"GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(
+            result
+                == b"HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: 15\r\n\
+                     Connection: close\r\nServer: 404-container\r\n\r\n{\"status\":\"ok\"}"
+        );
+    }
+
+    #[test]
+    fn test_serialize() {
+        let request = RequestMessage::from(b"GET / HTTP/1.1\r\n\r\n".as_slice());
+        let response = ResponseMessage {
+            body: Cow::Borrowed(BODY),
+            ..ResponseMessage::from_code(404)
+        };
+
+        let mut result = Vec::new();
+        let rest = serialize(
+            &request,
+            &response,
+            |bytes| push(bytes, &[CLOSE]),
+            &mut result,
+        );
+
+        assert!(rest.is_empty());
+        assert!(
+            result
+                == b"HTTP/1.1 404 Not Found\r\nContent-Length: 22\r\nConnection: close\r\n\r\n\
+                <h1>404 Not Found</h1>"
+        );
+    }
+
+    #[test]
+    fn test_serialize_head() {
+        let request = RequestMessage::from(b"HEAD / HTTP/1.1\r\n\r\n".as_slice());
+        let response = ResponseMessage {
+            body: Cow::Borrowed(BODY),
+            ..ResponseMessage::from_code(404)
+        };
+
+        let mut result = Vec::new();
+        let rest = serialize(
+            &request,
+            &response,
+            |bytes| push(bytes, &[CLOSE]),
+            &mut result,
+        );
+
+        assert!(rest.is_empty());
+        assert!(
+            result == b"HTTP/1.1 404 Not Found\r\nContent-Length: 22\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_large_body() {
+        let request = RequestMessage::from(b"GET / HTTP/1.1\r\n\r\n".as_slice());
+        let body = BODY.repeat(400);
+        let response = ResponseMessage {
+            body: Cow::Borrowed(&body),
+            ..ResponseMessage::from_code(404)
+        };
+
+        let mut result = Vec::new();
+        let rest = serialize(
+            &request,
+            &response,
+            |bytes| push(bytes, &[CLOSE]),
+            &mut result,
+        );
+
+        assert!(rest == body);
+        assert!(
+            result
+                == b"HTTP/1.1 404 Not Found\r\nContent-Length: 8800\r\nConnection: close\r\n\r\n"
+        );
+    }
+
+    #[test]
+    fn test_serialize_reusing_buffer() {
+        let request = RequestMessage::from(b"GET / HTTP/1.1\r\n\r\n".as_slice());
+        let response = ResponseMessage {
+            body: Cow::Borrowed(BODY),
+            ..ResponseMessage::from_code(404)
+        };
+        let mut result = Vec::new();
+
+        serialize(
+            &request,
+            &response,
+            |bytes| push(bytes, &[CLOSE]),
+            &mut result,
+        );
+        let expected = result.clone();
+        let (pointer, capacity) = (result.as_ptr(), result.capacity());
+
+        for _ in 0..3 {
+            result.clear();
+            serialize(
+                &request,
+                &response,
+                |bytes| push(bytes, &[CLOSE]),
+                &mut result,
+            );
+        }
+
+        assert!(result == expected);
+        assert!(result.as_ptr() == pointer && result.capacity() == capacity);
+    }
+
+    #[test]
+    fn test_process_with_stalled_request() {
+        let config = Arc::new(Config {
+            read_timeout: Duration::from_millis(50),
+            ..Config::default()
+        });
+
+        let result = task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+
+            client.write_all(b"GET / HTTP/1.1").await.unwrap(); // no line terminator ever
+            process(stream, None, config).await.unwrap();
+
+            let mut bytes = Vec::new();
+            client.read_to_end(&mut bytes).await.unwrap();
+            bytes
+        });
+
+        assert!(result.starts_with(b"HTTP/1.1 408 Request Timeout\r\n"));
+        assert!(result.windows(CLOSE.len()).any(|window| window == CLOSE));
+    }
+
+    /// Handles given client bytes over a TCP connection with a given Config, and returns the
+    /// handling result along with the response bytes.
+    fn handle_over_tcp(config: Config, request: &[u8]) -> (io::Result<()>, Vec<u8>) {
+        task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+
+            client.write_all(request).await.unwrap();
+            let result = handle(stream, Arc::new(config)).await;
+
+            let mut bytes = Vec::new();
+            client.read_to_end(&mut bytes).await.ok();
+            (result, bytes)
+        })
+    }
+
+    #[test]
+    fn test_handle_with_fragmented_request() {
+        let request = b"GET /livez HTTP/1.1\r\nHost: example.com\r\nConnection: close\r\n\r\n";
+
+        let (result, bytes) = task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+            let handling = task::spawn(handle(stream, Arc::new(Config::default())));
+
+            // NOTE: every byte goes in a segment of its own, splitting each CRLF in two as well
+            client.set_nodelay(true).unwrap();
+            for byte in request {
+                client.write_all(&[*byte]).await.unwrap();
+                client.flush().await.unwrap();
+                task::sleep(Duration::from_millis(1)).await;
+            }
+
+            let mut bytes = Vec::new();
+            client.read_to_end(&mut bytes).await.ok();
+            (handling.await, bytes)
+        });
+
+        assert!(result.is_ok());
+        assert!(bytes.starts_with(b"HTTP/1.1 204 No Content\r\n"));
+    }
+
+    #[test]
+    fn test_handle_with_proxy_protocol() {
+        let config = Config {
+            proxy_protocol: true,
+            ..Config::default()
+        };
+
+        let (result, bytes) = handle_over_tcp(
+            config,
+            b"PROXY TCP4 192.0.2.1 127.0.0.1 56324 8080\r\nGET /livez HTTP/1.1\r\nConnection: close\r\n\r\n",
+        );
+
+        assert!(result.is_ok());
+        assert!(bytes.starts_with(b"HTTP/1.1 204 No Content\r\n"));
+    }
+
+    #[test]
+    fn test_handle_with_proxy_protocol_missing() {
+        let config = Config {
+            proxy_protocol: true,
+            ..Config::default()
+        };
+
+        let (result, bytes) = handle_over_tcp(config, b"GET /livez HTTP/1.1\r\n\r\n");
+
+        assert!(result.is_err_and(|err| err.kind() == ErrorKind::InvalidData));
+        assert!(bytes.is_empty());
+    }
+
+    /// Represents a stream of a client that has sent a request, and has hung up right after.
+    struct Hangup(Cursor<Vec<u8>>);
+
+    impl Read for Hangup {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl Write for Hangup {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Err(ErrorKind::BrokenPipe.into()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_process_with_client_gone() {
+        let stream = Hangup(Cursor::new(
+            b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n".to_vec(),
+        ));
+
+        let result = task::block_on(process(stream, None, Arc::new(Config::default())));
+
+        assert!(result.is_err_and(|err| is_disconnect(&err)));
+    }
+
+    #[test]
+    fn test_is_disconnect() {
+        assert!(is_disconnect(&ErrorKind::ConnectionReset.into()));
+        assert!(!is_disconnect(&ErrorKind::InvalidData.into()));
+        assert!(!is_disconnect(&ErrorKind::TimedOut.into()));
+    }
+
+    #[test]
+    fn test_check() {
+        let config = Config {
+            health: b"/custom".to_vec(),
+            ..Config::default()
+        };
+
+        assert!(check(&config) == 0);
+    }
+
+    #[test]
+    fn test_check_with_health_json() {
+        let config = Config {
+            health_json: true,
+            ..Config::default()
+        };
+
+        assert!(check(&config) == 0);
+    }
+
+    #[test]
+    fn test_check_with_unreachable_health() {
+        let config = Config {
+            health: b"/%zz".to_vec(),
+            ..Config::default()
+        };
+
+        assert!(check(&config) == 1);
+    }
+
+    #[test]
+    fn test_bind_with_port_zero() {
+        let addr = "127.0.0.1:0".parse().unwrap();
+
+        let (_listener, local) = bind(addr, &Config::default()).unwrap();
+
+        assert!(local.ip() == addr.ip());
+        assert!(local.port() != 0);
+    }
+
+    #[test]
+    fn test_bind_with_reuse_port() {
+        let config = Config {
+            reuse_port: true,
+            ..Config::default()
+        };
+
+        let (_listener, local) = bind("127.0.0.1:0".parse().unwrap(), &config).unwrap();
+        let result = bind(local, &config);
+
+        assert!(result.is_ok_and(|(_, other)| other == local));
+    }
+
+    #[test]
+    fn test_bind_without_reuse_port() {
+        let (_listener, local) = bind("127.0.0.1:0".parse().unwrap(), &Config::default()).unwrap();
+        let result = bind(local, &Config::default());
+
+        assert!(result.is_err_and(|err| err.kind() == ErrorKind::AddrInUse));
+    }
+
+    #[test]
+    fn test_bind_with_retries() {
+        let (listener, local) = bind("127.0.0.1:0".parse().unwrap(), &Config::default()).unwrap();
+        let config = Config {
+            bind_retries: 3,
+            log_level: Level::Quiet,
+            ..Config::default()
+        };
+
+        let result = task::block_on(async {
+            let release = task::spawn(async move {
+                task::sleep(Duration::from_millis(150)).await;
+                drop(listener);
+            });
+            let result = bind_with_retries(local, &config).await;
+            release.await;
+            result
+        });
+
+        assert!(result.is_ok_and(|(_, other)| other == local));
+    }
+
+    #[test]
+    fn test_bind_with_retries_exhausted() {
+        let (_listener, local) = bind("127.0.0.1:0".parse().unwrap(), &Config::default()).unwrap();
+        let config = Config {
+            bind_retries: 1,
+            log_level: Level::Quiet,
+            ..Config::default()
+        };
+        let start = Instant::now();
+
+        let result = task::block_on(bind_with_retries(local, &config));
+
+        assert!(result.is_err_and(|err| err.kind() == ErrorKind::AddrInUse));
+        assert!(start.elapsed() >= BIND_DELAY);
+    }
+
+    #[test]
+    fn test_serve_with_workers() {
+        let config = Config {
+            workers: 2,
+            ..Config::default()
+        };
+        let (first, local) = bind("127.0.0.1:0".parse().unwrap(), &config).unwrap();
+        let (second, _) = bind(local, &config).unwrap();
+        let shared = Arc::new(Shared::new(config));
+
+        for listener in [first, second] {
+            let shared = shared.clone();
+            task::spawn(async move { serve(listener.incoming(), shared).await });
+        }
+
+        // NOTE: whichever worker accepts a connection, it is processed the same way
+        for _ in 0..8 {
+            let mut output = Vec::new();
+
+            task::block_on(async {
+                let mut stream = TcpStream::connect(local).await.unwrap();
+                stream
+                    .write_all(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+                    .await
+                    .unwrap();
+                stream.read_to_end(&mut output).await.unwrap();
+            });
+
+            assert!(output.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_tune_with_nodelay() {
+        let (listener, local) = bind("127.0.0.1:0".parse().unwrap(), &Config::default()).unwrap();
+        let _client = task::block_on(TcpStream::connect(local)).unwrap();
+        let (stream, _) = task::block_on(listener.accept()).unwrap();
+
+        stream.tune(&Config::default());
+        assert!(stream.nodelay().unwrap());
+
+        stream.tune(&Config {
+            nodelay: false,
+            ..Config::default()
+        });
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[test]
+    fn test_tune_with_tcp_keepalive() {
+        let (listener, local) = bind("127.0.0.1:0".parse().unwrap(), &Config::default()).unwrap();
+        let _client = task::block_on(TcpStream::connect(local)).unwrap();
+        let (stream, _) = task::block_on(listener.accept()).unwrap();
+
+        stream.tune(&Config::default());
+        assert!(!SockRef::from(&stream).keepalive().unwrap());
+
+        stream.tune(&Config {
+            tcp_keepalive: Some(Duration::from_secs(30)),
+            ..Config::default()
+        });
+        assert!(SockRef::from(&stream).keepalive().unwrap());
+        #[cfg(target_os = "linux")]
+        {
+            let socket = SockRef::from(&stream);
+            assert!(socket.keepalive_time().unwrap() == Duration::from_secs(30));
+            assert!(socket.keepalive_interval().unwrap() == Duration::from_secs(30));
+        }
+    }
+
+    #[test]
+    fn test_reload_keeps_listening_address() {
+        let shared = Shared::new(Config {
+            addr: "127.0.0.1:1".parse().unwrap(),
+            unix: Some("/nonexistent/404.sock".into()),
+            ..Config::default()
+        });
+
+        reload(&shared);
+
+        let config = shared.load();
+
+        assert!(config.addr.port() == 1);
+        assert!(config.unix.as_deref() == Some(Path::new("/nonexistent/404.sock")));
+    }
+
+    #[test]
+    fn test_process() {
+        let mut stream = Duplex::new(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+        assert!(stream.output.ends_with(b"Server: 404-container\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_pipelined_requests() {
+        let mut stream = Duplex::new(b"GET /livez HTTP/1.1\r\n\r\nHEAD / HTTP/1.1\r\n\r\n");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let (first, second) = result.split_once("\r\n\r\n").unwrap();
+
+        assert!(first.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(first.contains("\r\nConnection: keep-alive\r\n"));
+        assert!(second.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(second.ends_with("\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_tls_handshake() {
+        let mut stream = Duplex::new(b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.matches("HTTP/1.1 ").count() == 1);
+        assert!(result.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(result.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_h2_preface() {
+        let mut stream = Duplex::new(b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n\x00\x00\x12\x04\x00");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.matches("HTTP/1.1 ").count() == 1);
+        assert!(result.starts_with("HTTP/1.1 505 HTTP Version Not Supported\r\n"));
+        assert!(result.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_http_1_0_keep_alive() {
+        let mut stream = Duplex::new(
+            b"GET / HTTP/1.0\r\nConnection: keep-alive\r\n\r\nGET / HTTP/1.0\r\n\r\nGET / HTTP/1.0\r\n\r\n",
+        );
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let (first, second) = result.split_once("\r\n\r\n").unwrap();
+
+        assert!(result.matches("HTTP/1.1 404 Not Found\r\n").count() == 2);
+        assert!(first.contains("\r\nConnection: keep-alive\r\n"));
+        assert!(second.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_connection_header() {
+        for (version, header, expected) in [
+            ("1.1", "", KEEP_ALIVE),
+            ("1.1", "Connection: keep-alive\r\n", KEEP_ALIVE),
+            ("1.1", "Connection: close\r\n", CLOSE),
+            ("1.0", "", CLOSE),
+            ("1.0", "Connection: keep-alive\r\n", KEEP_ALIVE),
+            ("1.0", "Connection: close\r\n", CLOSE),
+        ] {
+            let request = format!("GET / HTTP/{version}\r\n{header}\r\nGET / HTTP/1.1\r\n\r\n");
+            let mut stream = Duplex::new(request.as_bytes());
+
+            task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+            let result = String::from_utf8(stream.output).unwrap();
+            let (first, _) = result.split_once("\r\n\r\n").unwrap();
+            let count = if expected == CLOSE { 1 } else { 2 };
+
+            // NOTE: the header has to tell what is done, i.e. whether the next one is answered
+            assert!(first.contains(std::str::from_utf8(expected).unwrap()));
+            assert!(result.matches("HTTP/1.1 404 Not Found\r\n").count() == count);
+        }
+    }
+
+    #[test]
+    fn test_process_with_rate_limit() {
+        let mut stream = Duplex::new(
+            b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n\
+              GET /healthz HTTP/1.1\r\n\r\n",
+        );
+        let config = Config {
+            rate_limit: Some(Rate {
+                per_second: 1,
+                burst: 2,
+            }),
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let responses: Vec<&str> = result.split_inclusive("\r\n\r\n").collect();
+
+        assert!(responses.len() == 4);
+        assert!(responses[1].starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(responses[2].starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+        assert!(responses[2].contains("\r\nRetry-After: 1\r\n"));
+        assert!(responses[3].starts_with("HTTP/1.1 204 No Content\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_per_ip_limit() {
+        let config = Arc::new(Config {
+            per_ip_limit: Some(Rate {
+                per_second: 1,
+                burst: 1,
+            }),
+            ..Config::default()
+        });
+        let requests = b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n";
+
+        let result: Vec<String> = ["198.51.100.85", "198.51.100.86"]
+            .iter()
+            .map(|client| {
+                let mut stream = Duplex::new(requests);
+                let remote = Some(client.parse().unwrap());
+
+                task::block_on(process(&mut stream, remote, config.clone())).unwrap();
+                String::from_utf8(stream.output).unwrap()
+            })
+            .collect();
+
+        for output in result {
+            let responses: Vec<&str> = output.split_inclusive("\r\n\r\n").collect();
+
+            assert!(responses[0].starts_with("HTTP/1.1 404 Not Found\r\n"));
+            assert!(responses[1].starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+        }
+    }
+
+    #[test]
+    fn test_process_with_per_ip_limit_and_spoofed_forwarded_for() {
+        let mut stream = Duplex::new(
+            b"GET / HTTP/1.1\r\nX-Forwarded-For: 203.0.113.1, 198.51.100.87\r\n\r\n\
+              GET / HTTP/1.1\r\nX-Forwarded-For: 203.0.113.2, 198.51.100.87\r\n\r\n",
+        );
+        let config = Config {
+            per_ip_limit: Some(Rate {
+                per_second: 1,
+                burst: 1,
+            }),
+            trust_proxy: true,
+            ..Config::default()
+        };
+        let remote = Some(IpAddr::from([10, 0, 0, 1]));
+
+        task::block_on(process(&mut stream, remote, Arc::new(config))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let responses: Vec<&str> = result.split_inclusive("\r\n\r\n").collect();
+
+        assert!(responses[0].starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(responses[1].starts_with("HTTP/1.1 429 Too Many Requests\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_keepalive_max() {
+        let mut stream =
+            Duplex::new(b"GET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+        let config = Config {
+            keepalive_max: 2,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let (first, second) = result.split_once("\r\n\r\n").unwrap();
+
+        assert!(result.matches("HTTP/1.1 404 Not Found\r\n").count() == 2);
+        assert!(first.contains("\r\nConnection: keep-alive\r\n"));
+        assert!(second.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_strict_line_endings() {
+        let config = Arc::new(Config {
+            strict_line_endings: true,
+            ..Config::default()
+        });
+
+        for (data, expected) in [
+            (
+                b"GET / HTTP/1.1\r\n\r\n".as_slice(),
+                "HTTP/1.1 404 Not Found\r\n",
+            ),
+            (b"GET / HTTP/1.1\n\n", "HTTP/1.1 400 Bad Request\r\n"),
+            (
+                b"GET / HTTP/1.1\r\nHost: a\nX: b\r\n\r\n",
+                "HTTP/1.1 400 Bad Request\r\n",
+            ),
+        ] {
+            let mut stream = Duplex::new(data);
+
+            task::block_on(process(&mut stream, None, config.clone())).unwrap();
+
+            let result = String::from_utf8(stream.output).unwrap();
+
+            assert!(result.starts_with(expected));
+            assert!(result.matches("HTTP/1.1 ").count() == 1);
+        }
+    }
+
+    #[test]
+    fn test_process_with_lenient_line_endings() {
+        let mut stream = Duplex::new(b"GET / HTTP/1.1\n\nGET / HTTP/1.1\r\n\r\n");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.matches("HTTP/1.1 404 Not Found\r\n").count() == 2);
+    }
+
+    #[test]
+    fn test_process_with_idle_connection() {
+        let config = Arc::new(Config {
+            keepalive: Duration::from_millis(50),
+            ..Config::default()
+        });
+
+        let result = task::block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let mut client = TcpStream::connect(listener.local_addr().unwrap())
+                .await
+                .unwrap();
+            let (stream, _) = listener.accept().await.unwrap();
+
+            client.write_all(b"GET / HTTP/1.1\r\n\r\n").await.unwrap(); // and then nothing
+            process(stream, None, config).await.unwrap();
+
+            let mut bytes = Vec::new();
+            client.read_to_end(&mut bytes).await.unwrap();
+            bytes
+        });
+
+        assert!(result.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+        assert!(result
+            .windows(KEEP_ALIVE.len())
+            .any(|window| window == KEEP_ALIVE));
+        assert!(
+            result
+                .windows(5)
+                .filter(|window| window == b"HTTP/")
+                .count()
+                == 1
+        );
+    }
+
+    #[test]
+    fn test_process_with_extra_headers() {
+        let mut stream = Duplex::new(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            extra_headers: vec![b"X-Frame-Options: DENY".to_vec()],
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream
+            .output
+            .ends_with(b"\r\nX-Frame-Options: DENY\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_cors_origin() {
+        let mut stream = Duplex::new(b"GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            cors_origin: Some(b"Access-Control-Allow-Origin: https://example.com".to_vec()),
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 204 "));
+        assert!(String::from_utf8(stream.output)
+            .unwrap()
+            .contains("\r\nAccess-Control-Allow-Origin: https://example.com\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_redirect() {
+        let mut stream = Duplex::new(
+            b"GET /foo HTTP/1.1\r\nHost: example.com\r\nX-Forwarded-Proto: http\r\nConnection: close\r\n\r\n",
+        );
+        let config = Config {
+            force_https: true,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let output = String::from_utf8(stream.output).unwrap();
+
+        assert!(output.starts_with("HTTP/1.1 308 Permanent Redirect\r\n"));
+        assert!(output.contains("\r\nLocation: https://example.com/foo\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_response_delay() {
+        let config = Arc::new(Config {
+            response_delay: Duration::from_millis(50),
+            ..Config::default()
+        });
+
+        let mut stream = Duplex::new(b"GET /slow HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let start = Instant::now();
+        task::block_on(process(&mut stream, None, config.clone())).unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(50));
+        assert!(stream.output.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+
+        let mut stream = Duplex::new(b"GET /healthz HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let start = Instant::now();
+        task::block_on(process(&mut stream, None, config)).unwrap();
+
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert!(stream.output.starts_with(b"HTTP/1.1 204 No Content\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_retry_after() {
+        let mut stream = Duplex::new(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            default: ResponseMessage::from_code(503),
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let output = String::from_utf8(stream.output).unwrap();
+        let value = output
+            .split("\r\n")
+            .find_map(|line| line.strip_prefix("Retry-After: "))
+            .unwrap();
+
+        assert!(output.starts_with("HTTP/1.1 503 "));
+        assert!(value.parse::<u64>().is_ok());
+    }
+
+    #[test]
+    fn test_process_without_retry_after() {
+        let mut stream = Duplex::new(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            default: ResponseMessage::from_code(503),
+            retry_after: None,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(!String::from_utf8(stream.output)
+            .unwrap()
+            .contains("Retry-After"));
+    }
+
+    #[test]
+    fn test_process_with_compression() {
+        let body = b"<p>404 Not Found</p>".repeat(100);
+        let mut stream =
+            Duplex::new(b"GET / HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            default: ResponseMessage {
+                body: Cow::Owned(body.clone()),
+                ..RESP_404
+            },
+            compress: true,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let split = stream
+            .output
+            .windows(4)
+            .position(|w| w == b"\r\n\r\n")
+            .unwrap();
+        let (head, payload) = (&stream.output[..split], &stream.output[split + 4..]);
+        let head = String::from_utf8(head.to_vec()).unwrap();
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(payload)
+            .read_to_end(&mut decoded)
+            .unwrap();
+
+        assert!(head.contains("\r\nContent-Encoding: gzip\r\n"));
+        assert!(head.contains("\r\nVary: Accept-Encoding"));
+        assert!(head.contains(&format!("\r\nContent-Length: {}\r\n", payload.len())));
+        assert!(decoded == body);
+    }
+
+    #[test]
+    fn test_process_with_compression_not_accepted() {
+        let body = b"<p>404 Not Found</p>".repeat(100);
+        let mut stream = Duplex::new(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            default: ResponseMessage {
+                body: Cow::Owned(body.clone()),
+                ..RESP_404
+            },
+            compress: true,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream.output.ends_with(&body));
+        assert!(!stream.output.windows(16).any(|w| w == b"Content-Encoding"));
+    }
+
+    #[test]
+    fn test_process_with_compression_and_small_body() {
+        let mut stream =
+            Duplex::new(b"GET / HTTP/1.1\r\nAccept-Encoding: gzip\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            compress: true,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(!stream.output.windows(16).any(|w| w == b"Content-Encoding"));
+        assert!(!stream.output.windows(4).any(|w| w == b"Vary"));
+    }
+
+    #[test]
+    fn test_process_with_request_body() {
+        let mut stream = Duplex::new(
+            b"POST / HTTP/1.1\r\nContent-Length: 21\r\n\r\nGET /livez HTTP/1.1\r\n\r\nGET /livez HTTP/1.1\r\n\r\n",
+        );
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let (first, second) = result.split_once("\r\n\r\n").unwrap();
+
+        assert!(result.matches("HTTP/1.1 ").count() == 2);
+        assert!(first.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(first.contains("\r\nConnection: keep-alive\r\n"));
+        assert!(second.starts_with("HTTP/1.1 204 No Content\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_continue_expected() {
+        let mut stream = Duplex::new(
+            b"POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\nConnection: close\r\n\r\nhello",
+        );
+        let config = Config {
+            root: Some(ResponseMessage::from_code(200)),
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream
+            .output
+            .starts_with(b"HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 200 OK\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_continue_expected_and_rejected() {
+        let mut stream =
+            Duplex::new(b"POST /foo HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\n");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        // NOTE: the body is never sent, so the connection cannot be kept alive past it
+        assert!(result.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(!result.contains("100 Continue"));
+        assert!(result.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_continue_expected_and_body_too_large() {
+        let mut stream = Duplex::new(
+            b"POST / HTTP/1.1\r\nExpect: 100-continue\r\nContent-Length: 5\r\n\r\nhello",
+        );
+        let config = Config {
+            max_body_bytes: 4,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream
+            .output
+            .starts_with(b"HTTP/1.1 413 Content Too Large\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_request_body_too_large() {
+        let mut stream = Duplex::new(b"POST / HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello");
+        let config = Config {
+            max_body_bytes: 4,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.starts_with("HTTP/1.1 413 Content Too Large\r\n"));
+        assert!(result.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_chunked_request_body() {
+        let mut stream = Duplex::new(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n",
+        );
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.matches("HTTP/1.1 ").count() == 1);
+        assert!(result.starts_with("HTTP/1.1 501 Not Implemented\r\n"));
+        assert!(result.contains("\r\nConnection: close\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_chunked_request_body_pipelined() {
+        let mut stream = Duplex::new(
+            b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n\
+              17\r\nGET /livez HTTP/1.1\r\n\r\n\r\n0\r\n\r\nGET /livez HTTP/1.1\r\n\r\n",
+        );
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        // NOTE: neither the smuggled request nor the next one may be answered
+        assert!(result.matches("HTTP/1.1 ").count() == 1);
+        assert!(!result.contains("204 No Content"));
+    }
+
+    #[test]
+    fn test_process_with_request_id() {
+        let mut stream =
+            Duplex::new(b"GET / HTTP/1.1\r\nX-Request-Id: given\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+        let (first, second) = result.split_once("\r\n\r\n").unwrap();
+
+        assert!(first.contains("\r\nX-Request-Id: given\r\n"));
+        assert!(second.contains("\r\nX-Request-Id: "));
+        assert!(!second.contains("\r\nX-Request-Id: given\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_tiny_read_buffer() {
+        let mut stream = Duplex::new(b"GET /livez HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+        let config = Config {
+            read_buffer_bytes: 1,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.starts_with("HTTP/1.1 204 No Content\r\n"));
+        assert!(result.contains("HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_malformed_request() {
+        let mut stream = Duplex::new(b"GET /\xff HTTP/1.1\r\n\r\nGET / HTTP/1.1\r\n\r\n");
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        let result = String::from_utf8(stream.output).unwrap();
+
+        assert!(result.matches("HTTP/1.1 ").count() == 1);
+        assert!(result.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+        assert!(result.contains("\r\nConnection: close\r\n"));
+    }
+
+    /// Creates a request line of a given length, including its line terminator.
+    fn request_line(len: usize) -> Vec<u8> {
+        let mut line = b"GET /".to_vec();
+        line.resize(len - b" HTTP/1.1\r\n".len(), b'a');
+        line.extend_from_slice(b" HTTP/1.1\r\n");
+        line
+    }
+
+    #[test]
+    fn test_process_with_path_at_limit() {
+        let mut input = [
+            b"OPTIONS /".as_slice(),
+            &[b'a'; RequestMessage::PATH_LIMIT - 1],
+        ]
+        .concat();
+        input.extend_from_slice(b" HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let mut stream = Duplex::new(&input);
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_request_line_at_limit() {
+        // NOTE: a short method leaves the path more room than it is allowed to take
+        let mut input = request_line(LIMIT);
+        input.extend_from_slice(b"Connection: close\r\n\r\n");
+        let mut stream = Duplex::new(&input);
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 414 URI Too Long\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_request_line_over_limit() {
+        let mut stream = Duplex::new(&request_line(LIMIT + 1));
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 414 URI Too Long\r\n"));
+        assert!(stream
+            .output
+            .windows(CLOSE.len())
+            .any(|window| window == CLOSE));
+    }
+
+    #[test]
+    fn test_process_with_path_at_max_uri_bytes() {
+        let mut stream = Duplex::new(b"GET /abcdefghijklmno HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            max_uri_bytes: 16,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 404 Not Found\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_path_over_max_uri_bytes() {
+        let mut stream =
+            Duplex::new(b"GET /abcdefghijklmnop HTTP/1.1\r\nConnection: close\r\n\r\n");
+        let config = Config {
+            max_uri_bytes: 16,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 414 URI Too Long\r\n"));
+    }
+
+    #[test]
+    fn test_process_with_request_line_over_max_uri_bytes() {
+        let mut stream = Duplex::new(&request_line(RequestMessage::line_limit(16) + 1));
+        let config = Config {
+            max_uri_bytes: 16,
+            ..Config::default()
+        };
+
+        task::block_on(process(&mut stream, None, Arc::new(config))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 414 URI Too Long\r\n"));
+        assert!(stream
+            .output
+            .windows(CLOSE.len())
+            .any(|window| window == CLOSE));
+    }
+
+    #[test]
+    fn test_process_with_request_line_boundaries() {
+        // NOTE: the longest method leaves the path exactly as much room as it is allowed to take
+        for (len, expected) in [
+            (LIMIT - 1, "HTTP/1.1 404 Not Found\r\n"),
+            (LIMIT, "HTTP/1.1 404 Not Found\r\n"),
+            (LIMIT + 1, "HTTP/1.1 414 URI Too Long\r\n"),
+        ] {
+            let mut input = b"OPTIONS /".to_vec();
+            input.resize(len - b" HTTP/1.1\r\n".len(), b'a');
+            input.extend_from_slice(b" HTTP/1.1\r\nConnection: close\r\n\r\n");
+            let mut stream = Duplex::new(&input);
+
+            task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+            assert!(stream.output.starts_with(expected.as_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_process_with_request_line_over_limit_by_method() {
+        // NOTE: the path alone fits, it is the line as a whole that does not
+        let mut input = [
+            b"OPTIONSX /".as_slice(),
+            &[b'a'; RequestMessage::PATH_LIMIT - 1],
+        ]
+        .concat();
+        input.extend_from_slice(b" HTTP/1.1\r\n\r\n");
+        let mut stream = Duplex::new(&input);
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        assert!(input.len() - 2 == LIMIT + 1);
+        assert!(stream.output.starts_with(b"HTTP/1.1 414 URI Too Long\r\n"));
+        assert!(stream
+            .output
+            .windows(CLOSE.len())
+            .any(|window| window == CLOSE));
+    }
+
+    #[test]
+    fn test_process_with_request_line_without_terminator() {
+        let mut stream = Duplex::new(&vec![b'G'; LIMIT]);
+
+        task::block_on(process(&mut stream, None, Arc::new(Config::default()))).unwrap();
+
+        assert!(stream.output.starts_with(b"HTTP/1.1 414 URI Too Long\r\n"));
+    }
+}
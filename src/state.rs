@@ -0,0 +1,111 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use async_std::task;
+
+const STEP: Duration = Duration::from_millis(10);
+
+/// Whether the server is ready to accept traffic, as reported by the readiness endpoint.
+pub static READY: AtomicBool = AtomicBool::new(true);
+
+/// Whether the server is shutting down, so no connection should be kept alive.
+pub static SHUTDOWN: AtomicBool = AtomicBool::new(false);
+
+/// The number of connections being processed at the moment.
+pub static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+/// The moment ticks are counted from, i.e. roughly when the server has started.
+static START: LazyLock<Instant> = LazyLock::new(Instant::now);
+
+/// Milliseconds since the start an accept loop has last gone around at.
+static TICK: AtomicU64 = AtomicU64::new(0);
+
+/// Represents a connection being processed, counted as in-flight for as long as it lives.
+pub struct Flight;
+
+impl Flight {
+    /// Creates a new Flight, incrementing the in-flight count.
+    pub fn start() -> Flight {
+        IN_FLIGHT.fetch_add(1, Ordering::Relaxed);
+        Flight
+    }
+}
+
+impl Drop for Flight {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// Returns how long the server has been running for.
+pub fn uptime() -> Duration {
+    START.elapsed()
+}
+
+/// Records that an accept loop is still going around.
+pub fn tick() {
+    let millis = u64::try_from(START.elapsed().as_millis()).unwrap_or(u64::MAX);
+    TICK.store(millis, Ordering::Relaxed);
+}
+
+/// Returns whether no accept loop has gone around for longer than a given duration.
+///
+/// Accept loops stop for good on shutdown, so it never counts as a stall.
+pub fn is_stalled(limit: Duration) -> bool {
+    let last = Duration::from_millis(TICK.load(Ordering::Relaxed));
+
+    !SHUTDOWN.load(Ordering::Relaxed) && START.elapsed().saturating_sub(last) > limit
+}
+
+/// Sleeps for a given duration, or until shutting down, whichever comes first.
+pub async fn sleep(duration: Duration) {
+    let deadline = Instant::now() + duration;
+
+    while !SHUTDOWN.load(Ordering::Relaxed) {
+        match deadline.checked_duration_since(Instant::now()) {
+            Some(remaining) if !remaining.is_zero() => task::sleep(remaining.min(STEP)).await,
+            _ => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flight() {
+        let flight = Flight::start();
+
+        assert!(IN_FLIGHT.load(Ordering::Relaxed) >= 1);
+
+        let count = IN_FLIGHT.load(Ordering::Relaxed);
+        drop(flight);
+
+        assert!(IN_FLIGHT.load(Ordering::Relaxed) < count);
+    }
+
+    #[test]
+    fn test_is_stalled() {
+        tick();
+
+        assert!(!is_stalled(Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn test_uptime() {
+        let before = uptime();
+
+        assert!(uptime() >= before);
+    }
+
+    #[test]
+    fn test_sleep() {
+        let start = Instant::now();
+
+        task::block_on(sleep(Duration::from_millis(30)));
+
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+}
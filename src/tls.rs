@@ -0,0 +1,73 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+use async_tls::TlsAcceptor;
+use rustls::{Certificate, PrivateKey, ServerConfig};
+use rustls_pemfile::Item;
+
+/// Reads all PEM items from a given file.
+fn read_items(path: &Path) -> Result<Vec<Item>, String> {
+    let file = File::open(path).map_err(|err| format!("Cannot read {}: {err}", path.display()))?;
+
+    rustls_pemfile::read_all(&mut BufReader::new(file))
+        .map_err(|err| format!("Cannot parse {}: {err}", path.display()))
+}
+
+/// Creates a new TlsAcceptor from a PEM certificate chain and a PEM private key.
+pub fn acceptor(cert: &Path, key: &Path) -> Result<TlsAcceptor, String> {
+    let certs: Vec<Certificate> = read_items(cert)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect();
+
+    if certs.is_empty() {
+        return Err("No TLS certificate found".into());
+    }
+
+    let key = read_items(key)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or("No TLS key found")?;
+
+    let config = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)
+        .map_err(|err| format!("Invalid TLS certificate or key: {err}"))?;
+
+    Ok(TlsAcceptor::from(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_acceptor_with_missing_file() {
+        let path = Path::new("/nonexistent/cert.pem");
+
+        let result = acceptor(path, path);
+
+        assert!(result.is_err_and(|err| err.starts_with("Cannot read")));
+    }
+
+    #[test]
+    fn test_acceptor_without_certificate() {
+        let path = env::temp_dir().join("http-404-test-empty.pem");
+        fs::write(&path, b"not a pem file").unwrap();
+
+        let result = acceptor(&path, &path);
+
+        assert!(result.is_err_and(|err| err == "No TLS certificate found"));
+    }
+}
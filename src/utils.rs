@@ -0,0 +1,721 @@
+use std::future;
+use std::io::{ErrorKind, IoSlice, Write};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_std::io::{self, BufRead, BufReadExt, Read, ReadExt, WriteExt};
+use flate2::write::GzEncoder;
+use flate2::{Compression, Crc};
+
+use crate::CRLF;
+
+const DAYS: [&str; 7] = ["Thu", "Fri", "Sat", "Sun", "Mon", "Tue", "Wed"];
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+// NOTE: the longest possible PROXY protocol v1 header, as per its specification
+const PROXY_LIMIT: usize = 107;
+
+// NOTE: Crockford's alphabet, i.e. no letters easy to mistake for digits
+const BASE32: &[u8; 32] = b"0123456789abcdefghjkmnpqrstvwxyz";
+
+static EPOCH: LazyLock<u64> = LazyLock::new(|| {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_nanos() as u64)
+        .unwrap_or_default()
+});
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+static RANDOM: AtomicU64 = AtomicU64::new(0);
+
+/// Encodes a given number as base32 digits filling a given slice, most significant first.
+fn base32(number: u64, digits: &mut [u8]) {
+    for (index, digit) in digits.iter_mut().rev().enumerate() {
+        *digit = BASE32[(number.checked_shr(5 * index as u32).unwrap_or_default() & 31) as usize];
+    }
+}
+
+/// Generates a new request ID from the process start time and a sequence number.
+///
+/// IDs are unique within the process, and unlikely to repeat across processes.
+pub fn request_id() -> [u8; 26] {
+    let mut id = [0; 26];
+    let (epoch, sequence) = id.split_at_mut(13);
+
+    base32(*EPOCH, epoch);
+    base32(SEQUENCE.fetch_add(1, Ordering::Relaxed), sequence);
+
+    id
+}
+
+/// Generates a pseudo-random number in the `[0, 1)` range, seeded with the process start time.
+///
+/// It is a SplitMix64 generator, cheap and good enough for sampling, but not for secrets.
+pub fn random() -> f64 {
+    let mut state = EPOCH.wrapping_add(
+        RANDOM
+            .fetch_add(1, Ordering::Relaxed)
+            .wrapping_mul(0x9e3779b97f4a7c15),
+    );
+
+    state = (state ^ (state >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    state = (state ^ (state >> 27)).wrapping_mul(0x94d049bb133111eb);
+    state ^= state >> 31;
+
+    // NOTE: the top 53 bits are as many as an f64 mantissa holds
+    (state >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Splits a given time into days since the epoch, a civil date, and seconds of the day.
+fn civil(time: SystemTime) -> (u64, u64, u64, u64, u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    let (days, rest) = (secs / 86400, secs % 86400);
+
+    // NOTE: a civil-from-days conversion, see http://howardhinnant.github.io/date_algorithms.html
+    let shifted = days + 719468;
+    let era = shifted / 146097;
+    let doe = shifted % 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+
+    (days, year, month, day, rest)
+}
+
+/// Appends a given time as an RFC 1123 date, e.g. `Sun, 06 Nov 1994 08:49:37 GMT`, to a given
+/// buffer.
+pub fn write_http_date(bytes: &mut Vec<u8>, time: SystemTime) {
+    let (days, year, month, day, rest) = civil(time);
+
+    // NOTE: writing to a vector never fails, it is the formatting that is of use here
+    Write::write_fmt(
+        bytes,
+        format_args!(
+            "{}, {:02} {} {} {:02}:{:02}:{:02} GMT",
+            DAYS[(days % 7) as usize],
+            day,
+            MONTHS[(month - 1) as usize],
+            year,
+            rest / 3600,
+            rest % 3600 / 60,
+            rest % 60,
+        ),
+    )
+    .ok();
+}
+
+/// Formats a given time as an RFC 3339 timestamp, e.g. `1994-11-06T08:49:37Z`.
+pub fn iso_date(time: SystemTime) -> String {
+    let (_, year, month, day, rest) = civil(time);
+
+    format!(
+        "{}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+    )
+}
+
+/// Compresses given bytes with gzip.
+pub fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::with_capacity(bytes.len() / 2), Compression::default());
+
+    // NOTE: writing to a vector cannot fail
+    encoder.write_all(bytes).expect("in-memory write");
+    encoder.finish().expect("in-memory write")
+}
+
+/// Computes an entity tag of given bytes, quoted, from their length and CRC-32 checksum.
+///
+/// It only depends on the bytes, so every process serving the same ones agrees on it.
+pub fn etag(bytes: &[u8]) -> String {
+    let mut crc = Crc::new();
+    crc.update(bytes);
+
+    format!("\"{:x}-{:08x}\"", bytes.len(), crc.sum())
+}
+
+/// Represents an outcome of reading a request head.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Head {
+    /// The head has been read up to a blank line or the end of stream.
+    Complete,
+    /// The request line has not fit its limit, and has been cut.
+    Truncated,
+    /// The header section has not fit its limit, and has been cut.
+    Overflowed,
+    /// The head does not look like an HTTP one at all, and has not been read.
+    Rejected,
+}
+
+/// Reads a line, up to a given limit, appending it to a buffer.
+///
+/// Returns `false` if the limit was hit before a line feed.
+async fn read_line<R>(reader: &mut R, buffer: &mut Vec<u8>, limit: usize) -> io::Result<bool>
+where
+    R: BufRead + Unpin,
+{
+    let start = buffer.len();
+    let read = reader
+        .take(limit as u64)
+        .read_until(CRLF[1], buffer)
+        .await?;
+
+    Ok(read < limit || buffer[start..].ends_with(&CRLF[1..]))
+}
+
+/// Returns the next byte without consuming it, or `None` at the end of stream.
+async fn peek<R>(reader: &mut R) -> io::Result<Option<u8>>
+where
+    R: BufRead + Unpin,
+{
+    future::poll_fn(|cx| {
+        Pin::new(&mut *reader)
+            .poll_fill_buf(cx)
+            .map_ok(|buf| buf.first().copied())
+    })
+    .await
+}
+
+/// Checks if a given byte may start a request line, i.e. a method, or a line terminator.
+fn is_plausible(byte: u8) -> bool {
+    byte.is_ascii_alphabetic() || CRLF.contains(&byte)
+}
+
+/// Checks if a given line is a blank one, i.e. a bare line terminator.
+fn is_blank(line: &[u8]) -> bool {
+    line == CRLF || line == &CRLF[1..]
+}
+
+/// Checks if every line terminator in a given head is a full CRLF, i.e. neither a bare CR nor a
+/// bare LF, which servers and proxies in a chain could disagree on.
+pub fn is_crlf_terminated(head: &[u8]) -> bool {
+    head.iter().enumerate().all(|(index, byte)| match byte {
+        b'\r' => head.get(index + 1) == Some(&b'\n'),
+        b'\n' => index > 0 && head[index - 1] == b'\r',
+        _ => true,
+    })
+}
+
+/// Reads a request head, i.e. a request line and header lines up to a blank one, into a buffer.
+///
+/// The request line and the header section have their own limits. Leading empty lines are
+/// skipped, and an end of stream ends the head as is. Bytes that cannot start a request line,
+/// e.g. a TLS handshake, are rejected before reading anything.
+pub async fn extract<R>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    limits: (usize, usize),
+) -> io::Result<Head>
+where
+    R: BufRead + Unpin,
+{
+    let (line_limit, header_limit) = limits;
+
+    loop {
+        if peek(reader).await?.is_some_and(|byte| !is_plausible(byte)) {
+            return Ok(Head::Rejected);
+        } else if !read_line(reader, buffer, line_limit).await? {
+            return Ok(Head::Truncated);
+        } else if !is_blank(buffer) {
+            break;
+        }
+        buffer.clear();
+    }
+
+    let start = buffer.len();
+
+    while buffer.ends_with(&CRLF[1..]) {
+        let offset = buffer.len();
+        let remaining = header_limit.saturating_sub(offset - start);
+
+        if !read_line(reader, buffer, remaining).await? {
+            return Ok(Head::Overflowed);
+        } else if is_blank(&buffer[offset..]) {
+            break;
+        }
+    }
+
+    Ok(Head::Complete)
+}
+
+/// Reads and discards a given number of bytes, e.g. a request body.
+///
+/// Returns `false` if the stream has ended before all of them.
+pub async fn drain<R>(reader: &mut R, length: u64) -> io::Result<bool>
+where
+    R: Read + Unpin,
+{
+    let drained = io::copy(&mut reader.take(length), &mut io::sink()).await?;
+
+    Ok(drained == length)
+}
+
+/// Writes all given slices, with as few vectored writes as a writer takes to write them.
+///
+/// Partial writes are picked up where they have stopped, so every byte is written exactly once.
+pub async fn write_all_vectored<W>(writer: &mut W, mut slices: &mut [IoSlice<'_>]) -> io::Result<()>
+where
+    W: io::Write + Unpin,
+{
+    IoSlice::advance_slices(&mut slices, 0); // i.e. skips the empty ones upfront
+
+    while !slices.is_empty() {
+        match writer.write_vectored(slices).await {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(written) => IoSlice::advance_slices(&mut slices, written),
+            Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses a PROXY protocol v1 header line, e.g. `PROXY TCP4 1.2.3.4 5.6.7.8 1234 80\r\n`.
+///
+/// Returns the source address, `Some(None)` if the proxy does not know it, or `None` if the
+/// line is malformed.
+fn parse_proxy(line: &[u8]) -> Option<Option<IpAddr>> {
+    let line = std::str::from_utf8(line.strip_suffix(CRLF)?).ok()?;
+    let mut fields = line.strip_prefix("PROXY ")?.split(' ');
+
+    let v4 = match fields.next()? {
+        "TCP4" => true,
+        "TCP6" => false,
+        "UNKNOWN" => return Some(None), // the rest of the line is to be ignored
+        _ => return None,
+    };
+    let mut addr = || {
+        let text = fields.next()?;
+        text.parse::<IpAddr>()
+            .ok()
+            .filter(|addr| addr.is_ipv4() == v4)
+    };
+
+    let source = addr()?;
+    addr()?;
+
+    for _ in 0..2 {
+        fields.next()?.parse::<u16>().ok()?;
+    }
+
+    fields.next().is_none().then_some(Some(source))
+}
+
+/// Reads a PROXY protocol v1 header byte by byte, so nothing past it is consumed.
+///
+/// Returns the source address it carries, if known, or an invalid data error if malformed.
+pub async fn read_proxy<R>(reader: &mut R) -> io::Result<Option<IpAddr>>
+where
+    R: Read + Unpin,
+{
+    let mut line = Vec::with_capacity(PROXY_LIMIT);
+    let mut byte = [0];
+
+    while line.len() < PROXY_LIMIT && !line.ends_with(&CRLF[1..]) {
+        reader.read_exact(&mut byte).await?;
+        line.push(byte[0]);
+
+        // NOTE: no reason to wait for the rest of it if it is not a header at all
+        if !b"PROXY ".starts_with(&line[..line.len().min(6)]) {
+            break;
+        }
+    }
+
+    parse_proxy(&line).ok_or_else(|| io::Error::new(ErrorKind::InvalidData, "Invalid PROXY header"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read as _;
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    use std::net::Ipv4Addr;
+
+    use async_std::io::{BufReader, Cursor};
+    use async_std::task;
+
+    use super::*;
+
+    #[test]
+    fn test_etag() {
+        let result = etag(b"<h1>404 Not Found</h1>");
+
+        assert!(result == etag(b"<h1>404 Not Found</h1>"));
+        assert!(result != etag(b"<h1>404 Not Found!</h1>"));
+        assert!(result.starts_with("\"16-") && result.ends_with('"') && result.len() == 13);
+    }
+
+    #[test]
+    fn test_gzip() {
+        let bytes = b"404 Not Found ".repeat(100);
+
+        let result = gzip(&bytes);
+
+        let mut decoded = Vec::new();
+        flate2::read::GzDecoder::new(result.as_slice())
+            .read_to_end(&mut decoded)
+            .unwrap();
+
+        assert!(result.starts_with(&[0x1f, 0x8b]));
+        assert!(result.len() < bytes.len());
+        assert!(decoded == bytes);
+    }
+
+    #[test]
+    fn test_random() {
+        let samples: Vec<f64> = (0..1000).map(|_| random()).collect();
+        let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+
+        assert!(samples.iter().all(|sample| (0.0..1.0).contains(sample)));
+        assert!((0.4..0.6).contains(&mean));
+    }
+
+    #[test]
+    fn test_http_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+
+        let mut result = Vec::new();
+        write_http_date(&mut result, time);
+
+        assert!(result == b"Sun, 06 Nov 1994 08:49:37 GMT");
+    }
+
+    #[test]
+    fn test_http_date_epoch() {
+        let mut result = Vec::new();
+        write_http_date(&mut result, UNIX_EPOCH);
+
+        assert!(result == b"Thu, 01 Jan 1970 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_http_date_leap_day() {
+        let time = UNIX_EPOCH + Duration::from_secs(1709164800);
+
+        let mut result = Vec::new();
+        write_http_date(&mut result, time);
+
+        assert!(result == b"Thu, 29 Feb 2024 00:00:00 GMT");
+    }
+
+    #[test]
+    fn test_base32() {
+        let mut digits = [0; 13];
+
+        base32(u64::MAX, &mut digits);
+
+        assert!(&digits == b"fzzzzzzzzzzzz");
+
+        base32(32 * 32 + 33, &mut digits);
+
+        assert!(&digits == b"0000000000111");
+    }
+
+    #[test]
+    fn test_request_id() {
+        let (first, second) = (request_id(), request_id());
+
+        assert!(first != second);
+        assert!(first[..13] == second[..13]);
+        assert!(first.iter().all(|byte| BASE32.contains(byte)));
+    }
+
+    #[test]
+    fn test_iso_date() {
+        let time = UNIX_EPOCH + Duration::from_secs(784111777);
+
+        let result = iso_date(time);
+
+        assert!(result == "1994-11-06T08:49:37Z");
+    }
+
+    #[test]
+    fn test_drain() {
+        let mut reader = Cursor::new(b"body=1GET / HTTP/1.1\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(drain(&mut reader, 6)).unwrap();
+
+        assert!(result);
+
+        task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(buffer == b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_drain_with_end_of_stream() {
+        let mut reader = Cursor::new(b"body");
+
+        let result = task::block_on(drain(&mut reader, 6)).unwrap();
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_extract() {
+        let mut reader =
+            Cursor::new(b"GET / HTTP/1.1\r\nHost: test\r\n\r\nGET /next HTTP/1.1\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET / HTTP/1.1\r\nHost: test\r\n\r\n");
+
+        buffer.clear();
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET /next HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_extract_with_leading_empty_lines() {
+        let mut reader = Cursor::new(b"\r\n\nGET / HTTP/1.1\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_extract_with_split_line_terminators() {
+        let bytes = b"GET / HTTP/1.1\r\nHost: test\r\n\r\nGET /next HTTP/1.1\r\n\r\n";
+        let mut reader = BufReader::with_capacity(3, Cursor::new(bytes));
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET / HTTP/1.1\r\nHost: test\r\n\r\n");
+
+        buffer.clear();
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET /next HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_extract_with_bare_carriage_return() {
+        let mut reader = Cursor::new(b"GET /a\rb HTTP/1.1\r\nHost: te\rst\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET /a\rb HTTP/1.1\r\nHost: te\rst\r\n\r\n");
+    }
+
+    #[test]
+    fn test_extract_with_end_of_stream() {
+        let mut reader = Cursor::new(b"GET / HTTP/1.1");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET / HTTP/1.1");
+    }
+
+    #[test]
+    fn test_extract_with_tls_handshake() {
+        let mut reader = Cursor::new(b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 1024))).unwrap();
+
+        assert!(result == Head::Rejected);
+        assert!(buffer.is_empty());
+    }
+
+    #[test]
+    fn test_extract_with_limit() {
+        let mut reader = Cursor::new(b"GET /too-long-path HTTP/1.1\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (8, 1024))).unwrap();
+
+        assert!(result == Head::Truncated);
+        assert!(buffer == b"GET /too");
+    }
+
+    #[test]
+    fn test_extract_with_exact_limit() {
+        let mut reader = Cursor::new(b"GET / HTTP/1.1\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (16, 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == b"GET / HTTP/1.1\r\n\r\n");
+    }
+
+    #[test]
+    fn test_extract_with_limit_inside_line_terminator() {
+        let mut reader = Cursor::new(b"GET / HTTP/1.1\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (15, 1024))).unwrap();
+
+        assert!(result == Head::Truncated);
+        assert!(buffer == b"GET / HTTP/1.1\r");
+    }
+
+    #[test]
+    fn test_is_crlf_terminated() {
+        assert!(is_crlf_terminated(b"GET / HTTP/1.1\r\nHost: test\r\n\r\n"));
+        assert!(is_crlf_terminated(b"GET / HTTP/1.1"));
+        assert!(!is_crlf_terminated(b"GET / HTTP/1.1\n\n"));
+        assert!(!is_crlf_terminated(b"GET / HTTP/1.1\r\nHost: test\n\r\n"));
+        assert!(!is_crlf_terminated(b"GET / HTTP/1.1\rHost: test\r\n\r\n"));
+        assert!(!is_crlf_terminated(b"\nGET / HTTP/1.1\r\n\r\n"));
+    }
+
+    #[test]
+    fn test_extract_with_max_length_path() {
+        let path = [b'a'; u16::MAX as usize];
+        let line = [b"GET /".as_slice(), &path, b" HTTP/1.1\r\n\r\n"].concat();
+        let mut reader = Cursor::new(line.clone());
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (line.len(), 1024))).unwrap();
+
+        assert!(result == Head::Complete);
+        assert!(buffer == line);
+    }
+
+    /// Represents a writer taking up to a few bytes at a time, as a congested socket would.
+    struct Trickle {
+        output: Vec<u8>,
+        writes: usize,
+    }
+
+    impl io::Write for Trickle {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let length = buf.len().min(5);
+
+            self.output.extend_from_slice(&buf[..length]);
+            self.writes += 1;
+            Poll::Ready(Ok(length))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[test]
+    fn test_write_all_vectored() {
+        let mut writer = Trickle {
+            output: Vec::new(),
+            writes: 0,
+        };
+        let mut slices = [
+            IoSlice::new(b""),
+            IoSlice::new(b"HTTP/1.1 404 Not Found\r\n\r\n"),
+            IoSlice::new(b""),
+            IoSlice::new(b"<h1>404 Not Found</h1>"),
+        ];
+
+        task::block_on(write_all_vectored(&mut writer, &mut slices)).unwrap();
+
+        assert!(writer.output == b"HTTP/1.1 404 Not Found\r\n\r\n<h1>404 Not Found</h1>");
+        assert!(writer.writes == 11);
+    }
+
+    #[test]
+    fn test_read_proxy() {
+        let mut reader = Cursor::new(b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\r\nGET /");
+
+        let result = task::block_on(read_proxy(&mut reader)).unwrap();
+
+        assert!(result == Some(IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1))));
+        assert!(reader.position() == 45);
+    }
+
+    #[test]
+    fn test_read_proxy_with_ipv6() {
+        let mut reader = Cursor::new(b"PROXY TCP6 2001:db8::1 2001:db8::2 56324 443\r\n");
+
+        let result = task::block_on(read_proxy(&mut reader)).unwrap();
+
+        assert!(result == Some("2001:db8::1".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_read_proxy_with_unknown() {
+        let mut reader = Cursor::new(b"PROXY UNKNOWN ffff::1 ffff::2 1 2\r\n");
+
+        let result = task::block_on(read_proxy(&mut reader)).unwrap();
+
+        assert!(result.is_none());
+    }
+
+    #[test]
+    fn test_read_proxy_malformed() {
+        for bytes in [
+            b"GET / HTTP/1.1\r\n\r\n".as_slice(),
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324\r\n",
+            b"PROXY TCP4 2001:db8::1 198.51.100.1 56324 443\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 65536\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443 extra\r\n",
+            b"PROXY TCP4 192.0.2.1 198.51.100.1 56324 443\n",
+            b"PROXY UDP4 192.0.2.1 198.51.100.1 56324 443\r\n",
+        ] {
+            let mut reader = Cursor::new(bytes);
+
+            let result = task::block_on(read_proxy(&mut reader));
+
+            assert!(result.is_err_and(|err| err.kind() == ErrorKind::InvalidData));
+        }
+    }
+
+    #[test]
+    fn test_read_proxy_over_limit() {
+        let mut reader = Cursor::new([b"PROXY ".as_slice(), &[b'a'; 200]].concat());
+
+        let result = task::block_on(read_proxy(&mut reader));
+
+        assert!(result.is_err_and(|err| err.kind() == ErrorKind::InvalidData));
+        assert!(reader.position() == PROXY_LIMIT as u64);
+    }
+
+    #[test]
+    fn test_extract_with_header_limit() {
+        let mut reader = Cursor::new(b"GET / HTTP/1.1\r\nX-Flood: aaaaaaaaaaaaaaaa\r\n\r\n");
+        let mut buffer = Vec::new();
+
+        let result = task::block_on(extract(&mut reader, &mut buffer, (1024, 16))).unwrap();
+
+        assert!(result == Head::Overflowed);
+        assert!(buffer == b"GET / HTTP/1.1\r\nX-Flood: aaaaaaa");
+    }
+}
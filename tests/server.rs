@@ -0,0 +1,227 @@
+//! Integration tests running the binary, and talking to it over real sockets.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::process::{Child, Command, Stdio};
+
+/// Represents a running binary, listening on a port picked by the OS.
+struct Server {
+    child: Child,
+    addr: String,
+}
+
+impl Server {
+    /// Starts the binary with given environment variables, and waits until it listens.
+    fn start(vars: &[(&str, &str)]) -> Server {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_http-404"))
+            .env_clear()
+            .envs([("BIND", "127.0.0.1"), ("PORT", "0")])
+            .envs(vars.iter().copied())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        // NOTE: the first line is e.g. `Listening on 127.0.0.1:34567 (http)`
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+
+        let addr = line
+            .strip_prefix("Listening on ")
+            .and_then(|rest| rest.split(' ').next())
+            .unwrap()
+            .to_string();
+
+        Server { child, addr }
+    }
+
+    /// Sends given request bytes over a new connection, and returns all the response bytes.
+    ///
+    /// The `Date` and `X-Request-Id` headers differ from run to run, so they are left out.
+    fn exchange(&self, request: &[u8]) -> Vec<u8> {
+        let mut stream = TcpStream::connect(&self.addr).unwrap();
+
+        stream.write_all(request).unwrap();
+        stream.shutdown(Shutdown::Write).unwrap(); // so a kept-alive connection ends too
+
+        let mut bytes = Vec::new();
+        stream.read_to_end(&mut bytes).unwrap();
+
+        let lines: Vec<&[u8]> = bytes
+            .split_inclusive(|char| char == &b'\n')
+            .filter(|line| !line.starts_with(b"Date: ") && !line.starts_with(b"X-Request-Id: "))
+            .collect();
+
+        lines.concat()
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+/// Returns a response head with a given status line, as it is with the default configuration.
+fn head(status: &str, connection: &str) -> Vec<u8> {
+    format!(
+        "HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: {connection}\r\n\
+         Server: 404-container\r\n\r\n"
+    )
+    .into_bytes()
+}
+
+#[test]
+fn test_get() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"GET /foo HTTP/1.1\r\nHost: example.com\r\n\r\n");
+
+    assert!(result == head("404 Not Found", "keep-alive"));
+}
+
+#[test]
+fn test_get_health() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"GET /healthz HTTP/1.1\r\n\r\n");
+
+    assert!(
+        result
+            == b"HTTP/1.1 204 No Content\r\nConnection: keep-alive\r\n\
+                 Server: 404-container\r\n\r\n"
+    );
+}
+
+#[test]
+fn test_refused_method() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"CONNECT example.com:443 HTTP/1.1\r\n\r\n");
+
+    assert!(
+        result
+            == b"HTTP/1.1 405 Method Not Allowed\r\n\
+                 Allow: GET, HEAD, POST, PUT, DELETE, OPTIONS, PATCH\r\n\
+                 Content-Length: 0\r\nConnection: keep-alive\r\nServer: 404-container\r\n\r\n"
+    );
+}
+
+#[test]
+fn test_unknown_method() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"PROPFIND / HTTP/1.1\r\n\r\n");
+
+    assert!(result == head("501 Not Implemented", "keep-alive"));
+}
+
+#[test]
+fn test_unsupported_version() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"GET / HTTP/1.2\r\n\r\n");
+
+    assert!(result == head("505 HTTP Version Not Supported", "close"));
+}
+
+#[test]
+fn test_over_long_path() {
+    let server = Server::start(&[("MAX_URI_BYTES", "16")]);
+
+    let result = server.exchange(b"GET /abcdefghijklmnop HTTP/1.1\r\n\r\n");
+
+    assert!(result == head("414 URI Too Long", "close"));
+}
+
+#[test]
+fn test_garbage() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"\x16\x03\x01\x02\x00\x01\x00\x01\xfc\x03\x03");
+
+    assert!(result == head("400 Bad Request", "close"));
+}
+
+#[test]
+fn test_empty() {
+    let server = Server::start(&[]);
+
+    let result = server.exchange(b"");
+
+    assert!(result == head("400 Bad Request", "close"));
+}
+
+#[test]
+fn test_pipelined_requests() {
+    let server = Server::start(&[]);
+
+    let result =
+        server.exchange(b"GET /a HTTP/1.1\r\n\r\nGET /b HTTP/1.1\r\nConnection: close\r\n\r\n");
+
+    assert!(
+        result
+            == [
+                head("404 Not Found", "keep-alive"),
+                head("404 Not Found", "close")
+            ]
+            .concat()
+    );
+}
+
+#[test]
+fn test_stats() {
+    let server = Server::start(&[("STATS", "1")]);
+
+    server.exchange(b"GET /foo HTTP/1.1\r\n\r\n");
+    server.exchange(b"GET /bar HTTP/1.1\r\n\r\n");
+    server.exchange(b"GET /healthz HTTP/1.1\r\n\r\n");
+    let result = String::from_utf8(server.exchange(b"GET /stats HTTP/1.1\r\n\r\n")).unwrap();
+
+    assert!(result.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(result.contains("\r\nContent-Type: text/plain; charset=utf-8\r\n"));
+    assert!(result.contains("\r\n\r\nuptime_seconds: "));
+    assert!(result.contains(
+        "\nrequests: 3\nrequests_1xx: 0\nrequests_2xx: 1\nrequests_3xx: 0\nrequests_4xx: 2\n\
+         requests_5xx: 0\nin_flight: "
+    ));
+}
+
+#[test]
+fn test_not_modified() {
+    let server = Server::start(&[("ROOT_BODY", "Hello")]);
+
+    let result = server.exchange(b"GET / HTTP/1.1\r\nIf-None-Match: \"5-f7d18982\"\r\n\r\n");
+
+    assert!(
+        result
+            == b"HTTP/1.1 304 Not Modified\r\nConnection: keep-alive\r\nETag: \"5-f7d18982\"\r\n\
+                 Server: 404-container\r\n\r\n"
+    );
+}
+
+#[test]
+fn test_validate() {
+    let run = |vars: &[(&str, &str)]| {
+        Command::new(env!("CARGO_BIN_EXE_http-404"))
+            .arg("--validate")
+            .env_clear()
+            .envs(vars.iter().copied())
+            .output()
+            .unwrap()
+    };
+
+    let valid = run(&[("ROUTES", "/api=503")]);
+    let invalid = run(&[("ROUTES", "/api=5O3")]);
+
+    assert!(valid.status.success());
+    assert!(valid
+        .stdout
+        .starts_with(b"Configuration is valid\nlisten: 0.0.0.0:8080\n"));
+    assert!(String::from_utf8_lossy(&valid.stdout).contains("\nroutes: /api=503\n"));
+    assert!(invalid.status.code() == Some(1));
+    assert!(invalid.stdout.is_empty());
+    assert!(invalid.stderr == b"Invalid route status; Quitting\n");
+}